@@ -1,6 +1,6 @@
 use chrono::{Local, Timelike};
 use colored::*;
-use network_changed::{NetworkChange, NetworkObserver, ObserverConfig};
+use network_changed::{NetworkObserver, ObserverConfig};
 use std::{thread, time};
 
 fn main() {
@@ -15,7 +15,7 @@ fn main() {
     println!("Listenign for changes, press Ctrl+C to cancel...");
     loop {
         let state = observer.state_change();
-        if state != NetworkChange::None {
+        if state.is_change() {
             let now = Local::now();
             println!(
                 "{} - Network changed: {}",
@@ -10,21 +10,13 @@ use std::{thread, time};
 fn on_change_callback(state: &NetworkChange, old: &NetworkState, new: &NetworkState) {
     let description = match state {
         NetworkChange::DefaultInterface => {
-            let old = old
-                .default_interface
-                .as_ref()
-                .map(|i| i.name.as_str())
-                .unwrap_or("None");
-            let new = new
-                .default_interface
-                .as_ref()
-                .map(|i| i.name.as_str())
-                .unwrap_or("None");
+            let old = old.default_interface.as_ref().map(|i| &i.name).to_string();
+            let new = new.default_interface.as_ref().map(|i| &i.name).to_string();
             format!("{} -> {}", old.yellow().bold(), new.yellow().bold())
         }
         NetworkChange::SecondaryInterface => {
-            let old = old.all_interfaces.as_ref().unwrap();
-            let new = new.all_interfaces.as_ref().unwrap();
+            let old = old.all_interfaces.observed().unwrap();
+            let new = new.all_interfaces.observed().unwrap();
             let diff = Interfaces::diff(old, new);
             let added = diff
                 .added
@@ -55,19 +47,46 @@ fn on_change_callback(state: &NetworkChange, old: &NetworkState, new: &NetworkSt
                 .red();
             format!("~[{}], +[{}], -[{}]", updated, added, removed)
         }
+        NetworkChange::InterfaceAdded(name) => {
+            let new = new.all_interfaces.observed().unwrap();
+            format!("+[{}] {}", name.bold().green(), new.describe(name))
+        }
+        NetworkChange::InterfaceRemoved(name) => {
+            let old = old.all_interfaces.observed().unwrap();
+            format!("-[{}] {}", name.bold().red(), old.describe(name))
+        }
         NetworkChange::PublicAddress => {
-            let old = old
-                .public_address
-                .as_ref()
-                .map(|i| i.to_string())
-                .unwrap_or("None".to_string());
-            let new = new
-                .public_address
-                .as_ref()
-                .map(|i| i.to_string())
-                .unwrap_or("None".to_string());
+            let old = old.public_address.to_string();
+            let new = new.public_address.to_string();
             format!("{} -> {}", old.yellow().bold(), new.yellow().bold())
         }
+        NetworkChange::DnsConfig => {
+            let old = old.dns_config.observed().cloned().unwrap_or_default();
+            let new = new.dns_config.observed().cloned().unwrap_or_default();
+            let diff = old.diff(&new);
+            let join = |items: Vec<String>| items.join(", ");
+            format!(
+                "nameservers +[{}] -[{}], search +[{}] -[{}]",
+                join(
+                    diff.added_nameservers
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect()
+                )
+                .bold()
+                .green(),
+                join(
+                    diff.removed_nameservers
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect()
+                )
+                .bold()
+                .red(),
+                join(diff.added_search).bold().green(),
+                join(diff.removed_search).bold().red(),
+            )
+        }
         NetworkChange::Expired => {
             let diff = new
                 .last_update
@@ -95,6 +114,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let config = ObserverConfig::default()
         .enable_observe_public_address(true)
         .enable_observe_all_interfaces(true)
+        .enable_observe_dns_config(true)
         .set_on_change(on_change_callback);
     let mut observer = NetworkObserver::new(config);
     println!("Listenign for changes, press Ctrl+C to cancel...");
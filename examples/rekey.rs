@@ -0,0 +1,45 @@
+use network_changed::{
+    encryption::{rekey, EncryptionKey},
+    integrity::Integrity,
+    state_store::FileStore,
+    ObserverConfig,
+};
+use std::error::Error;
+
+/// Re-encrypt a persisted state file with a new key.
+///
+/// Usage: `rekey [STATE_FILE] [none|checksum|hmac]`
+///
+/// The old key is read from `NETWORK_CHANGED_OLD_KEY` and the new key from
+/// `NETWORK_CHANGED_KEY`; either falls back to the machine id when unset.
+/// The integrity trailer must match the observer's, the HMAC key is read from
+/// `NETWORK_CHANGED_HMAC_KEY`.
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let store = match std::env::args().nth(1) {
+        Some(path) => FileStore::new(path),
+        None => FileStore::default(),
+    };
+    let integrity = match std::env::args().nth(2).as_deref() {
+        None | Some("none") => Integrity::None,
+        Some("checksum") => Integrity::Checksum,
+        Some("hmac") => Integrity::Hmac(std::env::var("NETWORK_CHANGED_HMAC_KEY")?.into_bytes()),
+        Some(other) => return Err(format!("Unknown integrity {}", other).into()),
+    };
+    let key = |name: &str| {
+        if std::env::var_os(name).is_some() {
+            EncryptionKey::Env(name.to_string())
+        } else {
+            EncryptionKey::MachineId
+        }
+    };
+
+    let path = store.path().clone();
+    let config = ObserverConfig::default()
+        .set_store(store)
+        .set_integrity(integrity)
+        .set_encryption_key(key("NETWORK_CHANGED_KEY"));
+    rekey(&config, &[key("NETWORK_CHANGED_OLD_KEY")])?;
+    println!("Re-encrypted {}", path.display());
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use chrono::{Local, Timelike};
 use colored::*;
-use network_changed::{NetworkChange, NetworkObserver, ObserverConfig};
+use network_changed::{NetworkObserver, ObserverConfig};
 use std::error::Error;
 use std::{thread, time};
 
@@ -18,7 +18,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     println!("Listenign for changes, press Ctrl+C to cancel...");
     loop {
         let state = observer.state_change().await;
-        if state != NetworkChange::None {
+        if state.is_change() {
             let now = Local::now();
             println!(
                 "{} - Network changed: {}",
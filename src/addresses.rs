@@ -0,0 +1,142 @@
+use crate::error::Result;
+use log::debug;
+use netdev::{ip::Ipv6Net, Interface};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, net::Ipv6Addr};
+
+#[cfg(target_os = "linux")]
+use crate::error::Error;
+
+/// Which IPv6 addresses of an interface are compared.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv6AddressFilter {
+    /// Compare all addresses.
+    #[default]
+    All,
+    /// Ignore temporary and deprecated addresses, such as privacy extension addresses.
+    Stable,
+    /// Compare only the prefixes of the addresses.
+    Prefixes,
+}
+
+impl Ipv6AddressFilter {
+    /// Filter the IPv6 addresses of `interface`, `unstable` lists the addresses ignored by
+    /// [`Stable`](Self::Stable).
+    pub fn apply(&self, interface: &mut Interface, unstable: &HashSet<Ipv6Addr>) {
+        match self {
+            Self::All => {}
+            Self::Stable => interface.ipv6.retain(|net| !unstable.contains(&net.addr)),
+            Self::Prefixes => {
+                for net in interface.ipv6.iter_mut() {
+                    *net = Ipv6Net::new(net.network(), net.prefix_len);
+                }
+                interface.ipv6.sort();
+                interface.ipv6.dedup();
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn unstable_ipv6_addresses() -> Result<HashSet<Ipv6Addr>> {
+    use futures::TryStreamExt;
+    use netlink_packet_route::{
+        address::{AddressAttribute, AddressFlags, AddressHeaderFlags},
+        AddressFamily,
+    };
+    use std::net::IpAddr;
+
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+    let mut addresses = handle.address().get().execute();
+    let mut unstable = HashSet::new();
+    while let Some(message) = addresses
+        .try_next()
+        .await
+        .map_err(|e| Error::NetlinkError(e.to_string()))?
+    {
+        if message.header.family != AddressFamily::Inet6 {
+            continue;
+        }
+        // IFA_F_TEMPORARY shares its value with IFA_F_SECONDARY
+        let mut is_unstable = message
+            .header
+            .flags
+            .intersects(AddressHeaderFlags::Secondary | AddressHeaderFlags::Deprecated);
+        let mut address = None;
+        for attribute in message.attributes {
+            match attribute {
+                AddressAttribute::Address(IpAddr::V6(a)) => address = Some(a),
+                AddressAttribute::Flags(flags) => {
+                    is_unstable |=
+                        flags.intersects(AddressFlags::Secondary | AddressFlags::Deprecated)
+                }
+                _ => {}
+            }
+        }
+        if let Some(address) = address.filter(|_| is_unstable) {
+            unstable.insert(address);
+        }
+    }
+    debug!("Temporary and deprecated IPv6 addresses: {:?}", unstable);
+    Ok(unstable)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn unstable_ipv6_addresses() -> Result<HashSet<Ipv6Addr>> {
+    debug!("IPv6 address flags are not supported");
+    Err(crate::error::Error::Unsupported(
+        "IPv6 address flags".to_string(),
+    ))
+}
+
+/// Get the temporary and deprecated IPv6 addresses of all interfaces.
+#[maybe_async::async_impl]
+pub async fn get_unstable_ipv6_addresses() -> Result<HashSet<Ipv6Addr>> {
+    unstable_ipv6_addresses().await
+}
+
+/// Get the temporary and deprecated IPv6 addresses of all interfaces.
+#[maybe_async::sync_impl]
+pub fn get_unstable_ipv6_addresses() -> Result<HashSet<Ipv6Addr>> {
+    crate::block_on(unstable_ipv6_addresses())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface(addresses: &[&str]) -> Interface {
+        let mut interface = Interface::dummy();
+        interface.ipv6 = addresses
+            .iter()
+            .map(|a| Ipv6Net::new(a.parse().unwrap(), 64))
+            .collect();
+        interface
+    }
+
+    #[test]
+    fn stable() {
+        let mut old = interface(&["2001:db8::1", "2001:db8::aaaa"]);
+        let mut new = interface(&["2001:db8::1", "2001:db8::bbbb"]);
+        let unstable: HashSet<Ipv6Addr> = ["2001:db8::aaaa", "2001:db8::bbbb"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        Ipv6AddressFilter::Stable.apply(&mut old, &unstable);
+        Ipv6AddressFilter::Stable.apply(&mut new, &unstable);
+        assert_eq!(old.ipv6, new.ipv6);
+        assert_eq!(old.ipv6, interface(&["2001:db8::1"]).ipv6);
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut old = interface(&["2001:db8::1", "2001:db8::aaaa"]);
+        let mut new = interface(&["2001:db8::bbbb"]);
+        Ipv6AddressFilter::Prefixes.apply(&mut old, &HashSet::new());
+        Ipv6AddressFilter::Prefixes.apply(&mut new, &HashSet::new());
+        assert_eq!(old.ipv6, new.ipv6);
+        assert_eq!(old.ipv6, interface(&["2001:db8::"]).ipv6);
+    }
+}
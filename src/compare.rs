@@ -0,0 +1,245 @@
+//! # ⚖️ Tuning which differences count as a network change
+
+use crate::{network_interfaces::Interfaces, routes::Route};
+use netdev::Interface;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Decides whether two observations of an interface or route are equal for change detection.
+pub trait Comparator: Debug + Send + Sync {
+    fn interface_eq(&self, old: &Interface, new: &Interface) -> bool;
+
+    fn route_eq(&self, old: &Route, new: &Route) -> bool;
+
+    /// Compare routing tables entry by entry, in order.
+    fn routes_eq(&self, old: &[Route], new: &[Route]) -> bool {
+        old.len() == new.len() && old.iter().zip(new).all(|(a, b)| self.route_eq(a, b))
+    }
+
+    /// Compare interfaces by name, link properties are always compared.
+    fn interfaces_eq(&self, old: &Interfaces, new: &Interfaces) -> bool {
+        old.eq_by(new, |a, b| self.interface_eq(a, b))
+    }
+}
+
+/// Fields of an interface that count toward change detection, all by default.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct InterfaceFields {
+    /// Name, friendly name and description.
+    pub name: bool,
+    pub index: bool,
+    pub if_type: bool,
+    pub mac_addr: bool,
+    pub ipv4: bool,
+    pub ipv6: bool,
+    /// Flags and whether the interface is the default one.
+    pub flags: bool,
+    /// Transmit and receive speed.
+    pub speed: bool,
+    /// Addresses of the gateway.
+    pub gateway: bool,
+    /// Link-layer address of the gateway.
+    pub gateway_mac: bool,
+    pub dns_servers: bool,
+}
+
+impl Default for InterfaceFields {
+    fn default() -> Self {
+        Self {
+            name: true,
+            index: true,
+            if_type: true,
+            mac_addr: true,
+            ipv4: true,
+            ipv6: true,
+            flags: true,
+            speed: true,
+            gateway: true,
+            gateway_mac: true,
+            dns_servers: true,
+        }
+    }
+}
+
+impl InterfaceFields {
+    /// Only the name and the IPv4 addresses.
+    pub fn name_and_ipv4() -> Self {
+        Self {
+            name: true,
+            ipv4: true,
+            ..Self::none()
+        }
+    }
+
+    /// No field, every interface with the same name is equal.
+    pub fn none() -> Self {
+        Self {
+            name: false,
+            index: false,
+            if_type: false,
+            mac_addr: false,
+            ipv4: false,
+            ipv6: false,
+            flags: false,
+            speed: false,
+            gateway: false,
+            gateway_mac: false,
+            dns_servers: false,
+        }
+    }
+
+    pub fn eq(&self, a: &Interface, b: &Interface) -> bool {
+        let gateway_ips =
+            |i: &Interface| i.gateway.as_ref().map(|g| (g.ipv4.clone(), g.ipv6.clone()));
+        let gateway_mac = |i: &Interface| i.gateway.as_ref().map(|g| g.mac_addr);
+        (!self.name
+            || (a.name == b.name
+                && a.friendly_name == b.friendly_name
+                && a.description == b.description))
+            && (!self.index || a.index == b.index)
+            && (!self.if_type || a.if_type == b.if_type)
+            && (!self.mac_addr || a.mac_addr == b.mac_addr)
+            && (!self.ipv4 || a.ipv4 == b.ipv4)
+            && (!self.ipv6 || a.ipv6 == b.ipv6)
+            && (!self.flags || (a.flags == b.flags && a.default == b.default))
+            && (!self.speed
+                || (a.transmit_speed == b.transmit_speed && a.receive_speed == b.receive_speed))
+            && (!self.gateway || gateway_ips(a) == gateway_ips(b))
+            && (!self.gateway_mac || gateway_mac(a) == gateway_mac(b))
+            && (!self.dns_servers || a.dns_servers == b.dns_servers)
+    }
+}
+
+/// Fields of a route that count toward change detection, all by default.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct RouteFields {
+    /// Destination and prefix.
+    pub destination: bool,
+    pub gateway: bool,
+    pub ifindex: bool,
+    pub metric: bool,
+    pub table: bool,
+    pub protocol: bool,
+    pub scope: bool,
+    /// Preferred source address.
+    pub source: bool,
+    /// Route type, such as unicast or unreachable.
+    pub kind: bool,
+}
+
+impl Default for RouteFields {
+    fn default() -> Self {
+        Self {
+            destination: true,
+            gateway: true,
+            ifindex: true,
+            metric: true,
+            table: true,
+            protocol: true,
+            scope: true,
+            source: true,
+            kind: true,
+        }
+    }
+}
+
+impl RouteFields {
+    pub fn eq(&self, a: &Route, b: &Route) -> bool {
+        (!self.destination || (a.destination == b.destination && a.prefix == b.prefix))
+            && (!self.gateway || a.gateway == b.gateway)
+            && (!self.ifindex || a.ifindex == b.ifindex)
+            && (!(self.gateway || self.ifindex) || a.nexthops == b.nexthops)
+            && (!self.metric || a.metric == b.metric)
+            && (!self.table || a.table == b.table)
+            && (!self.protocol || a.protocol == b.protocol)
+            && (!self.scope || a.scope == b.scope)
+            && (!self.source || a.source == b.source)
+            && (!self.kind || a.kind == b.kind)
+    }
+}
+
+/// Selects the fields of interfaces and routes that count toward change detection.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ComparePolicy {
+    pub interface: InterfaceFields,
+    pub route: RouteFields,
+}
+
+impl ComparePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interface_fields(mut self, fields: InterfaceFields) -> Self {
+        self.interface = fields;
+        self
+    }
+
+    pub fn with_route_fields(mut self, fields: RouteFields) -> Self {
+        self.route = fields;
+        self
+    }
+}
+
+impl Comparator for ComparePolicy {
+    fn interface_eq(&self, old: &Interface, new: &Interface) -> bool {
+        self.interface.eq(old, new)
+    }
+
+    fn route_eq(&self, old: &Route, new: &Route) -> bool {
+        self.route.eq(old, new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netdev::{ip::Ipv4Net, NetworkDevice};
+
+    fn interface(mac: u8) -> Interface {
+        let mut interface = Interface::dummy();
+        interface.name = "eth0".to_string();
+        interface.ipv4 = vec![Ipv4Net::new("192.168.1.2".parse().unwrap(), 24)];
+        let mut gateway = NetworkDevice::new();
+        gateway.mac_addr = netdev::mac::MacAddr::new(0, 0, 0, 0, 0, mac);
+        interface.gateway = Some(gateway);
+        interface
+    }
+
+    #[test]
+    fn default_policy_is_partial_eq() {
+        let policy = ComparePolicy::default();
+        assert!(policy.interface_eq(&interface(1), &interface(1)));
+        assert!(!policy.interface_eq(&interface(1), &interface(2)));
+    }
+
+    #[test]
+    fn ignore_fields() {
+        let policy = ComparePolicy::new().with_interface_fields(InterfaceFields {
+            gateway_mac: false,
+            ..Default::default()
+        });
+        assert!(policy.interface_eq(&interface(1), &interface(2)));
+
+        let mut renumbered = interface(1);
+        renumbered.index = 7;
+        renumbered.ipv6 = vec![netdev::ip::Ipv6Net::new("2001:db8::1".parse().unwrap(), 64)];
+        let policy = ComparePolicy::new().with_interface_fields(InterfaceFields::name_and_ipv4());
+        assert!(policy.interface_eq(&interface(1), &renumbered));
+
+        let route = |ifindex| Route::new("0.0.0.0".parse().unwrap(), 0, None, Some(ifindex));
+        let policy = ComparePolicy::new().with_route_fields(RouteFields {
+            ifindex: false,
+            ..Default::default()
+        });
+        assert!(policy.route_eq(&route(2), &route(3)));
+        assert!(!ComparePolicy::default().routes_eq(&[route(2)], &[route(3)]));
+
+        // failover to a route with another metric
+        let policy = ComparePolicy::default();
+        assert!(!policy.route_eq(&route(2).with_metric(100), &route(2).with_metric(600)));
+    }
+}
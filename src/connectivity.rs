@@ -0,0 +1,254 @@
+use crate::error::{Error, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Internet connectivity as seen by the connectivity check.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Connectivity {
+    /// The check returned the expected response.
+    Online,
+    /// The check was intercepted, with the redirect target if the portal redirected.
+    CaptivePortal(Option<String>),
+    /// The check URL could not be reached.
+    Offline,
+}
+
+/// HTTP request used to check connectivity, redirects are not followed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ConnectivityCheck {
+    pub url: String,
+    pub expected_status: u16,
+    /// Expected response body, ignoring surrounding whitespace.
+    pub expected_body: Option<String>,
+    /// Timeout in seconds.
+    pub timeout: u64,
+}
+
+impl Default for ConnectivityCheck {
+    fn default() -> Self {
+        Self::new("http://connectivitycheck.gstatic.com/generate_204")
+    }
+}
+
+impl ConnectivityCheck {
+    /// Check the given URL, expecting an empty `204 No Content` response.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            expected_status: 204,
+            expected_body: None,
+            timeout: 5,
+        }
+    }
+
+    pub fn with_expected_status(mut self, expected_status: u16) -> Self {
+        self.expected_status = expected_status;
+        self
+    }
+
+    pub fn with_expected_body(mut self, expected_body: impl Into<String>) -> Self {
+        self.expected_body = Some(expected_body.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// A redirect or a successful response with another body is a captive portal, other
+    /// responses such as server errors tell nothing about connectivity.
+    fn classify(
+        &self,
+        status: u16,
+        location: Option<String>,
+        body: Option<&str>,
+    ) -> Result<Connectivity> {
+        if (300..400).contains(&status) {
+            return Ok(Connectivity::CaptivePortal(location));
+        }
+        let body_matches = match (&self.expected_body, body) {
+            (Some(expected), Some(body)) => body.trim() == expected.trim(),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if status == self.expected_status && body_matches {
+            Ok(Connectivity::Online)
+        } else if status == self.expected_status || status == 200 {
+            Ok(Connectivity::CaptivePortal(None))
+        } else {
+            Err(Error::ConnectivityError(format!(
+                "Unexpected status {} from {}",
+                status, self.url
+            )))
+        }
+    }
+}
+
+fn location(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(reqwest::header::LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Perform the connectivity check.
+#[maybe_async::async_impl]
+pub async fn check_connectivity(check: &ConnectivityCheck) -> Result<Connectivity> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(check.timeout))
+        .build()
+        .map_err(|e| Error::ConnectivityError(e.to_string()))?;
+    let response = match client.get(&check.url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Connectivity check failed: {}", e);
+            return Ok(Connectivity::Offline);
+        }
+    };
+    let status = response.status().as_u16();
+    let location = location(response.headers());
+    let body = match check.expected_body {
+        Some(_) => response.text().await.ok(),
+        None => None,
+    };
+    let connectivity = check.classify(status, location, body.as_deref())?;
+    debug!("Connectivity: {:?}", connectivity);
+    Ok(connectivity)
+}
+
+/// Perform the connectivity check.
+#[maybe_async::sync_impl]
+pub fn check_connectivity(check: &ConnectivityCheck) -> Result<Connectivity> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(check.timeout))
+        .build()
+        .map_err(|e| Error::ConnectivityError(e.to_string()))?;
+    let response = match client.get(&check.url).send() {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("Connectivity check failed: {}", e);
+            return Ok(Connectivity::Offline);
+        }
+    };
+    let status = response.status().as_u16();
+    let location = location(response.headers());
+    let body = match check.expected_body {
+        Some(_) => response.text().ok(),
+        None => None,
+    };
+    let connectivity = check.classify(status, location, body.as_deref())?;
+    debug!("Connectivity: {:?}", connectivity);
+    Ok(connectivity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Serve a single canned HTTP response on a local port.
+    fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/check", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn online() {
+        let url = serve("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n");
+        let check = ConnectivityCheck::new(url);
+        assert_eq!(
+            check_connectivity(&check).await.unwrap(),
+            Connectivity::Online
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn online_with_body() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nsuccess\n");
+        let check = ConnectivityCheck::new(url)
+            .with_expected_status(200)
+            .with_expected_body("success");
+        assert_eq!(
+            check_connectivity(&check).await.unwrap(),
+            Connectivity::Online
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn captive_portal_redirect() {
+        let url = serve(
+            "HTTP/1.1 302 Found\r\nLocation: http://portal.example/login\r\nContent-Length: 0\r\n\r\n",
+        );
+        let check = ConnectivityCheck::new(url);
+        assert_eq!(
+            check_connectivity(&check).await.unwrap(),
+            Connectivity::CaptivePortal(Some("http://portal.example/login".to_string()))
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn captive_portal_intercepted() {
+        let url = serve("HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n<html></html>");
+        let check = ConnectivityCheck::new(url);
+        assert_eq!(
+            check_connectivity(&check).await.unwrap(),
+            Connectivity::CaptivePortal(None)
+        );
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn server_error() {
+        let url = serve("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+        let check = ConnectivityCheck::new(url);
+        assert!(matches!(
+            check_connectivity(&check).await,
+            Err(Error::ConnectivityError(_))
+        ));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn not_found() {
+        let url = serve("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        let check = ConnectivityCheck::new(url)
+            .with_expected_status(200)
+            .with_expected_body("success");
+        assert!(matches!(
+            check_connectivity(&check).await,
+            Err(Error::ConnectivityError(_))
+        ));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn offline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/check", listener.local_addr().unwrap());
+        drop(listener);
+        let check = ConnectivityCheck::new(url).with_timeout(1);
+        assert_eq!(
+            check_connectivity(&check).await.unwrap(),
+            Connectivity::Offline
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! # 🧭 DNS resolver configuration from resolv.conf
+
+use crate::error::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{io::ErrorKind, net::IpAddr};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+/// Upstream servers when `/etc/resolv.conf` points at the systemd-resolved stub.
+const RESOLVED_CONF: &str = "/run/systemd/resolve/resolv.conf";
+const RESOLVED_STUBS: [IpAddr; 2] = [
+    IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 53)),
+    IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 54)),
+];
+
+/// DNS resolver configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsConfig {
+    pub nameservers: Vec<IpAddr>,
+    pub search: Vec<String>,
+}
+
+impl DnsConfig {
+    /// Parse the contents of a `resolv.conf` file.
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // strip the scope of link-local addresses
+                    let address = words.next().and_then(|a| a.split('%').next());
+                    if let Some(Ok(address)) = address.map(str::parse) {
+                        config.nameservers.push(address);
+                    }
+                }
+                // the last search or domain line wins
+                Some("search") | Some("domain") => {
+                    config.search = words.map(str::to_string).collect();
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+
+    pub fn diff(&self, other: &Self) -> DnsConfigDiff {
+        DnsConfigDiff {
+            added_nameservers: missing_from(&other.nameservers, &self.nameservers),
+            removed_nameservers: missing_from(&self.nameservers, &other.nameservers),
+            added_search: missing_from(&other.search, &self.search),
+            removed_search: missing_from(&self.search, &other.search),
+        }
+    }
+
+    fn uses_resolved_stub(&self) -> bool {
+        !self.nameservers.is_empty()
+            && self
+                .nameservers
+                .iter()
+                .all(|address| RESOLVED_STUBS.contains(address))
+    }
+}
+
+fn missing_from<T: Clone + PartialEq>(items: &[T], other: &[T]) -> Vec<T> {
+    items
+        .iter()
+        .filter(|item| !other.contains(item))
+        .cloned()
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct DnsConfigDiff {
+    pub added_nameservers: Vec<IpAddr>,
+    pub removed_nameservers: Vec<IpAddr>,
+    pub added_search: Vec<String>,
+    pub removed_search: Vec<String>,
+}
+
+/// Read the resolver configuration, `None` if there is no `resolv.conf`.
+///
+/// When systemd-resolved's stub resolver is configured, its upstream configuration is used instead.
+pub fn get_dns_config() -> Result<Option<DnsConfig>> {
+    let config = match std::fs::read_to_string(RESOLV_CONF) {
+        Ok(contents) => DnsConfig::parse(&contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if config.uses_resolved_stub() {
+        if let Ok(contents) = std::fs::read_to_string(RESOLVED_CONF) {
+            let mut resolved = DnsConfig::parse(&contents);
+            debug!("DNS config from systemd-resolved:\n{:?}", resolved);
+            if resolved.search.is_empty() {
+                resolved.search = config.search;
+            }
+            return Ok(Some(resolved));
+        }
+    }
+    debug!("DNS config:\n{:?}", config);
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config = DnsConfig::parse(
+            "# generated\n\
+             nameserver 192.0.2.53\n\
+             nameserver fe80::1%eth0\n\
+             nameserver invalid\n\
+             domain example.org\n\
+             search corp.example.com example.com\n\
+             options edns0\n",
+        );
+        assert_eq!(
+            config,
+            DnsConfig {
+                nameservers: vec!["192.0.2.53".parse().unwrap(), "fe80::1".parse().unwrap()],
+                search: vec!["corp.example.com".to_string(), "example.com".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn resolved_stub() {
+        assert!(DnsConfig::parse("nameserver 127.0.0.53\n").uses_resolved_stub());
+        assert!(!DnsConfig::parse("nameserver 192.0.2.53\n").uses_resolved_stub());
+        assert!(!DnsConfig::parse("").uses_resolved_stub());
+    }
+
+    #[test]
+    fn diff() {
+        let old = DnsConfig::parse("nameserver 192.0.2.1\nnameserver 192.0.2.2\nsearch home\n");
+        let new = DnsConfig::parse("nameserver 192.0.2.2\nnameserver 192.0.2.3\nsearch office\n");
+        assert_eq!(
+            old.diff(&new),
+            DnsConfigDiff {
+                added_nameservers: vec!["192.0.2.3".parse().unwrap()],
+                removed_nameservers: vec!["192.0.2.1".parse().unwrap()],
+                added_search: vec!["office".to_string()],
+                removed_search: vec!["home".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! # 🔐 Encryption of the persisted state
+
+use crate::{
+    error::{Error, Result},
+    network_id::KnownNetworks,
+    NetworkState, ObserverConfig,
+};
+use cocoon::Cocoon;
+use log::{debug, trace};
+use std::{fmt, path::PathBuf, sync::Arc};
+
+type KeyCallback = Arc<dyn Fn() -> Result<Vec<u8>> + Send + Sync>;
+
+/// Source of the key used to encrypt the persisted state.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum EncryptionKey {
+    /// Key derived from the machine id.
+    #[default]
+    MachineId,
+    /// Raw key bytes.
+    Bytes(Vec<u8>),
+    /// Key read from the named environment variable.
+    Env(String),
+    /// Key read from a file, trailing whitespace is ignored.
+    File(PathBuf),
+    /// Key returned by a user supplied callback.
+    Callback(KeyCallback),
+}
+
+impl EncryptionKey {
+    pub fn callback(callback: impl Fn() -> Result<Vec<u8>> + Send + Sync + 'static) -> Self {
+        Self::Callback(Arc::new(callback))
+    }
+
+    /// Resolve the key bytes, failing if the key is unavailable or empty.
+    pub fn resolve(&self) -> Result<Vec<u8>> {
+        let key = match self {
+            Self::MachineId => mid::get(env!("CARGO_PKG_NAME"))
+                .map(String::into_bytes)
+                .map_err(|e| Error::EncryptionError(format!("Machine id unavailable: {:?}", e)))?,
+            Self::Bytes(bytes) => bytes.clone(),
+            Self::Env(name) => std::env::var(name).map(String::into_bytes).map_err(|e| {
+                Error::EncryptionError(format!("Key variable {} unavailable: {}", name, e))
+            })?,
+            Self::File(path) => {
+                let mut bytes = std::fs::read(path).map_err(|e| {
+                    Error::EncryptionError(format!(
+                        "Key file {} unavailable: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                let len = bytes
+                    .iter()
+                    .rposition(|b| !b.is_ascii_whitespace())
+                    .map_or(0, |i| i + 1);
+                bytes.truncate(len);
+                bytes
+            }
+            Self::Callback(callback) => callback()?,
+        };
+        if key.is_empty() {
+            return Err(Error::EncryptionError("Empty encryption key".to_string()));
+        }
+        Ok(key)
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MachineId => write!(f, "MachineId"),
+            Self::Bytes(_) => write!(f, "Bytes(..)"),
+            Self::Env(name) => f.debug_tuple("Env").field(name).finish(),
+            Self::File(path) => f.debug_tuple("File").field(path).finish(),
+            Self::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+impl PartialEq for EncryptionKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::MachineId, Self::MachineId) => true,
+            (Self::Bytes(a), Self::Bytes(b)) => a == b,
+            (Self::Env(a), Self::Env(b)) => a == b,
+            (Self::File(a), Self::File(b)) => a == b,
+            (Self::Callback(a), Self::Callback(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+fn cocoon(password: &[u8]) -> Cocoon<'_, cocoon::Creation> {
+    if cfg!(debug_assertions) {
+        Cocoon::new(password).with_weak_kdf()
+    } else {
+        Cocoon::new(password)
+    }
+}
+
+pub(crate) fn decrypt(data: Vec<u8>, key: &EncryptionKey) -> Result<Vec<u8>> {
+    trace!("Decrypting data");
+    let password = key.resolve()?;
+    match cocoon(&password).unwrap(&data) {
+        Ok(data) => Ok(data),
+        Err(e) => Err(Error::EncryptionError(format!("Error decrypting: {:?}", e))),
+    }
+}
+
+pub(crate) fn encrypt(data: Vec<u8>, key: &EncryptionKey) -> Result<Vec<u8>> {
+    trace!("Encrypting data");
+    let password = key.resolve()?;
+    match cocoon(&password).wrap(&data) {
+        Ok(data) => Ok(data),
+        Err(e) => Err(Error::EncryptionError(format!("Error encrypting: {:?}", e))),
+    }
+}
+
+/// Decrypt with the current key, falling back to the previous keys in order.
+///
+/// Returns the decrypted data and whether a previous key had to be used.
+pub(crate) fn decrypt_with_any(
+    data: Vec<u8>,
+    key: &EncryptionKey,
+    previous_keys: &[EncryptionKey],
+) -> Result<(Vec<u8>, bool)> {
+    let error = match decrypt(data.clone(), key) {
+        Ok(data) => return Ok((data, false)),
+        Err(e) => e,
+    };
+    for (i, previous_key) in previous_keys.iter().enumerate() {
+        match decrypt(data.clone(), previous_key) {
+            Ok(data) => {
+                debug!("Decrypted data with previous key #{}", i);
+                return Ok((data, true));
+            }
+            Err(e) => trace!("Previous key #{} failed: {}", i, e),
+        }
+    }
+    Err(error)
+}
+
+/// Re-encrypt the state and the known networks persisted with `config` with its encryption key.
+///
+/// They are decrypted with the configured key or the first of `old_keys` that works.
+/// Their integrity trailer is verified first and sealed again over the new data.
+pub fn rekey(config: &ObserverConfig, old_keys: &[EncryptionKey]) -> Result<()> {
+    let config = config
+        .clone()
+        .set_previous_encryption_keys(old_keys.to_vec());
+    NetworkState::load_with(&config)?.save_with(&config)?;
+    match KnownNetworks::load_with(&config) {
+        Ok(known) => known.save_with(&config),
+        Err(Error::StateNotFound) => Ok(()),
+        Err(Error::FileError(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrity::Integrity;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = EncryptionKey::Bytes(b"secret".to_vec());
+        let data = b"hello world".to_vec();
+        let encrypted = encrypt(data.clone(), &key).unwrap();
+        let decrypted = decrypt(encrypted.clone(), &key).unwrap();
+        assert_eq!(data, decrypted);
+
+        let wrong_key = EncryptionKey::callback(|| Ok(b"other".to_vec()));
+        assert!(matches!(
+            decrypt(encrypted, &wrong_key),
+            Err(Error::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_key() {
+        let key = EncryptionKey::Env("NETWORK_CHANGED_TEST_MISSING_KEY".to_string());
+        assert!(matches!(
+            encrypt(b"hello world".to_vec(), &key),
+            Err(Error::EncryptionError(_))
+        ));
+        assert!(matches!(
+            EncryptionKey::Bytes(Vec::new()).resolve(),
+            Err(Error::EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_file() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-key-file",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        std::fs::write(&path, b"secret\n").unwrap();
+        let key = EncryptionKey::File(path.clone());
+        assert_eq!(key.resolve().unwrap(), b"secret");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rekey() {
+        use crate::{network_id::NetworkId, state_store::MemoryStore, Observation};
+
+        let old_key = EncryptionKey::Bytes(b"old".to_vec());
+        let new_key = EncryptionKey::Bytes(b"new".to_vec());
+        let config = ObserverConfig::default()
+            .set_store(MemoryStore::new())
+            .set_integrity(Integrity::Hmac(b"secret".to_vec()))
+            .set_encryption_key(new_key.clone());
+        let mut state = NetworkState::new();
+        state.default_interface = Observation::Observed(netdev::Interface::dummy());
+        let old_config = config.clone().set_encryption_key(old_key.clone());
+        state.save_with(&old_config).unwrap();
+        // without known networks
+        rekey(&config, std::slice::from_ref(&old_key)).unwrap();
+        state.save_with(&old_config).unwrap();
+
+        let config = config.set_known_networks_store(MemoryStore::new());
+        let mut known = KnownNetworks::new();
+        known.insert(NetworkId::from_state(&state).unwrap(), "home");
+        known
+            .save_with(&config.clone().set_encryption_key(old_key.clone()))
+            .unwrap();
+
+        assert!(rekey(&config, &[]).is_err());
+        assert!(matches!(
+            rekey(
+                &config.clone().set_integrity(Integrity::Checksum),
+                std::slice::from_ref(&old_key)
+            ),
+            Err(Error::IntegrityError(_))
+        ));
+        rekey(&config, &[new_key, old_key.clone()]).unwrap();
+        assert_eq!(NetworkState::load_with(&config).unwrap(), state);
+        assert_eq!(KnownNetworks::load_with(&config).unwrap(), known);
+        let config = config.set_encryption_key(old_key);
+        assert!(NetworkState::load_with(&config).is_err());
+        assert!(KnownNetworks::load_with(&config).is_err());
+    }
+}
@@ -15,4 +15,27 @@ pub enum Error {
     FileError(#[from] std::io::Error),
     #[error("Encryption error")]
     EncryptionError(String),
+    #[error("Integrity check failed: {0}")]
+    IntegrityError(String),
+    #[error("Route probe failed: {0}")]
+    RouteError(std::io::Error),
+    #[error("Invalid route: {0}")]
+    InvalidRoute(String),
+    #[error("Public address lookup failed: {0}")]
+    PublicAddressError(#[from] public_ip_address::error::Error),
+    #[error("Netlink error: {0}")]
+    NetlinkError(String),
+    #[error("Network namespace error: {0}")]
+    NamespaceError(String),
+    #[error("Unsupported on this platform: {0}")]
+    Unsupported(String),
+    #[error("Connectivity check failed: {0}")]
+    ConnectivityError(String),
+    #[error("No persisted state found")]
+    StateNotFound,
+    #[error("Persisted state failed to load and is kept: {0}")]
+    StateNotLoaded(String),
+    #[cfg(feature = "sqlite")]
+    #[error("Database error")]
+    DatabaseError(#[from] rusqlite::Error),
 }
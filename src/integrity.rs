@@ -0,0 +1,124 @@
+//! # 🛡️ Integrity protection of the persisted state
+
+use crate::error::{Error, Result};
+use hmac::{Hmac, Mac};
+use log::trace;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+const TAG_LEN: usize = 32;
+
+/// Integrity trailer appended to the persisted state and verified on load.
+#[derive(Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub enum Integrity {
+    /// No trailer.
+    #[default]
+    None,
+    /// SHA-256 checksum, detects corruption but not deliberate edits.
+    Checksum,
+    /// HMAC-SHA256 keyed with the given secret, detects tampering.
+    Hmac(Vec<u8>),
+}
+
+impl fmt::Debug for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Checksum => write!(f, "Checksum"),
+            Self::Hmac(_) => write!(f, "Hmac(..)"),
+        }
+    }
+}
+
+impl Integrity {
+    fn tag(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self {
+            Self::None => Ok(None),
+            Self::Checksum => Ok(Some(Sha256::digest(data).to_vec())),
+            Self::Hmac(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key)
+                    .map_err(|e| Error::IntegrityError(format!("Invalid key: {}", e)))?;
+                mac.update(data);
+                Ok(Some(mac.finalize().into_bytes().to_vec()))
+            }
+        }
+    }
+
+    /// Append the integrity trailer to `data`.
+    pub(crate) fn seal(&self, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(tag) = self.tag(&data)? {
+            trace!("Appending integrity trailer");
+            data.extend_from_slice(&tag);
+        }
+        Ok(data)
+    }
+
+    /// Verify and strip the integrity trailer from `data`.
+    pub(crate) fn verify(&self, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if *self == Self::None {
+            return Ok(data);
+        }
+        trace!("Verifying integrity trailer");
+        if data.len() < TAG_LEN {
+            return Err(Error::IntegrityError("Missing trailer".to_string()));
+        }
+        let tag = data.split_off(data.len() - TAG_LEN);
+        let valid = match self {
+            Self::Hmac(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key)
+                    .map_err(|e| Error::IntegrityError(format!("Invalid key: {}", e)))?;
+                mac.update(&data);
+                // constant time comparison
+                mac.verify_slice(&tag).is_ok()
+            }
+            _ => self.tag(&data)?.is_some_and(|expected| expected == tag),
+        };
+        if !valid {
+            return Err(Error::IntegrityError("Trailer mismatch".to_string()));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_verify() {
+        for integrity in [
+            Integrity::None,
+            Integrity::Checksum,
+            Integrity::Hmac(b"secret".to_vec()),
+        ] {
+            let sealed = integrity.seal(b"hello world".to_vec()).unwrap();
+            assert_eq!(integrity.verify(sealed).unwrap(), b"hello world");
+        }
+    }
+
+    #[test]
+    fn detect_tampering() {
+        let integrity = Integrity::Checksum;
+        let mut sealed = integrity.seal(b"hello world".to_vec()).unwrap();
+        sealed[0] = b'j';
+        assert!(matches!(
+            integrity.verify(sealed),
+            Err(Error::IntegrityError(_))
+        ));
+        assert!(matches!(
+            integrity.verify(b"short".to_vec()),
+            Err(Error::IntegrityError(_))
+        ));
+
+        // recomputing the trailer requires the key
+        let integrity = Integrity::Hmac(b"secret".to_vec());
+        let forged = Integrity::Hmac(b"guess".to_vec())
+            .seal(b"hello world".to_vec())
+            .unwrap();
+        assert!(matches!(
+            integrity.verify(forged),
+            Err(Error::IntegrityError(_))
+        ));
+    }
+}
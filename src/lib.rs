@@ -1,20 +1,55 @@
+use addresses::Ipv6AddressFilter;
 use log::{trace, warn};
-pub use network_state::{Interfaces, NetworkState};
-pub use observer_config::ObserverConfig;
+use neighbours::MacAddr;
+use network_id::{KnownNetworks, NetworkId};
+pub use network_state::{Interfaces, NetworkState, Route};
+pub use observation::Observation;
+use observer_config::OnChange;
 use observer_config::DEFAULT_EXPIRE_TIME;
-use public_ip_address::lookup::LookupProvider;
-use std::time::Duration;
+pub use observer_config::{Autosave, ObserverConfig};
+use serde::{Deserialize, Serialize};
+pub use state_store::StateStore;
+use statistics::Statistics;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+use vpn::Vpn;
 
+pub mod addresses;
+pub mod compare;
+pub mod connectivity;
+pub mod dns;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod error;
+pub mod integrity;
+pub mod link;
+pub mod namespaces;
+pub mod neighbours;
+pub mod netns;
+pub mod network_id;
 pub mod network_interfaces;
 pub mod network_state;
+pub mod observation;
 pub mod observer_config;
+pub mod public_address;
 pub mod routes;
+pub mod rules;
+pub mod state_store;
+pub mod statistics;
+pub mod vpn;
+pub mod wireless;
 
 #[derive(Debug, PartialEq)]
 pub struct NetworkObserver {
     config: ObserverConfig,
     last_state: NetworkState,
+    known_networks: KnownNetworks,
+    last_checkpoint: Instant,
+    closed: bool,
+    load_error: Option<String>,
 }
 
 #[non_exhaustive]
@@ -23,27 +58,143 @@ pub enum NetworkChange {
     None,
     Expired,
     DefaultInterface,
+    /// An interface other than the default one changed. Where link properties are read,
+    /// on Linux, interfaces that appear or disappear are reported as
+    /// [`NetworkChange::InterfaceAdded`] and [`NetworkChange::InterfaceRemoved`] instead.
     SecondaryInterface,
+    /// The preferred default route changed.
     DefaultRoute,
+    /// The IPv4 default routes changed, but not the preferred default route.
+    DefaultRouteV4,
+    /// The IPv6 default routes changed, but not the preferred default route.
+    DefaultRouteV6,
     RoutingTable,
+    /// The route selected for the watched destination changed.
+    WatchedRoute(IpAddr),
+    /// The policy routing rules changed.
+    RoutingRules,
     PublicAddress,
+    DnsConfig,
+    Connectivity,
+    GatewayMac,
+    /// Joined a network labelled in the known networks.
+    JoinedKnownNetwork(String),
+    JoinedUnknownNetwork(NetworkId),
+    /// The SSID of the named wireless interface changed.
+    WifiSsid(String),
+    /// The named wireless interface roamed to another access point of the same SSID.
+    WifiRoamed(String),
+    /// The named interface appeared, see [`Interfaces::describe`].
+    InterfaceAdded(String),
+    /// The named interface disappeared.
+    InterfaceRemoved(String),
+    /// The named interface gained or lost carrier.
+    Carrier(String),
+    /// The operational state of the named interface changed.
+    OperState(String),
+    /// The named interface renegotiated its link speed.
+    LinkSpeed(String),
+    /// The named interface renegotiated its duplex mode.
+    Duplex(String),
+    /// The error rate of the named interface rose above the threshold.
+    ErrorRate(String),
+    /// The drop rate of the named interface rose above the threshold.
+    DropRate(String),
+    /// The traffic of the named interface fell below the idle threshold.
+    Idle(String),
+    /// Routes started pointing at a tunnel interface.
+    VpnConnected(Vpn),
+    /// Routes stopped pointing at a tunnel interface.
+    VpnDisconnected(Vpn),
+    /// The routes through a connected tunnel changed, such as from split to full tunnel.
+    VpnChanged(Vpn),
+    /// A probe failed and nothing else changed, the state was not updated.
+    ProbeFailed(Probe),
+}
+
+impl NetworkChange {
+    /// Whether this is an actual change of the network state.
+    pub fn is_change(&self) -> bool {
+        !matches!(self, NetworkChange::None | NetworkChange::ProbeFailed(_))
+    }
+}
+
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    DefaultRoute,
+    AllRoutes,
+    RoutingRules,
+    PublicAddress,
+    DnsConfig,
+    Connectivity,
+    GatewayMac,
+    Wireless,
+    Vpn,
+    /// The network namespace could not be entered.
+    Namespace,
 }
 
 impl NetworkObserver {
+    /// Create an observer, starting over if the persisted state cannot be loaded.
+    ///
+    /// A persisted state that exists but fails to load is never overwritten,
+    /// see [`NetworkObserver::load_error`]. Use [`NetworkObserver::try_new`] to fail instead.
     pub fn new(config: ObserverConfig) -> Self {
-        let current_state = if config.persist {
-            trace!("Loading state");
-            NetworkState::load().unwrap_or_else(|_| NetworkState::new())
-        } else {
+        match Self::load_state(&config) {
+            Ok(state) => Self::with_state(config, state),
+            Err(e) => {
+                warn!("Failed to load state, it will not be overwritten: {}", e);
+                let mut observer = Self::with_state(config, NetworkState::new());
+                observer.load_error = Some(e.to_string());
+                observer
+            }
+        }
+    }
+
+    /// Create an observer, failing if the persisted state exists but cannot be loaded,
+    /// for example because it was tampered with or the encryption key is missing.
+    pub fn try_new(config: ObserverConfig) -> error::Result<Self> {
+        let state = Self::load_state(&config)?;
+        Ok(Self::with_state(config, state))
+    }
+
+    /// Why the persisted state failed to load, if it did. Such an observer does not persist.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    fn load_state(config: &ObserverConfig) -> error::Result<NetworkState> {
+        if !config.persist {
             let mut s = NetworkState::new();
             // expire the state
             s.last_update -= Duration::from_secs(DEFAULT_EXPIRE_TIME);
-            s
+            return Ok(s);
+        }
+        trace!("Loading state");
+        match NetworkState::load_with(config) {
+            Err(error::Error::StateNotFound) => Ok(NetworkState::new()),
+            Err(error::Error::FileError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(NetworkState::new())
+            }
+            result => result,
+        }
+    }
+
+    fn with_state(config: ObserverConfig, last_state: NetworkState) -> Self {
+        let known_networks = if config.persist && config.observe_network_id {
+            KnownNetworks::load_with(&config).unwrap_or_default()
+        } else {
+            KnownNetworks::new()
         };
 
         NetworkObserver {
             config,
-            last_state: current_state,
+            last_state,
+            known_networks,
+            last_checkpoint: Instant::now(),
+            closed: false,
+            load_error: None,
         }
     }
 }
@@ -51,70 +202,402 @@ impl NetworkObserver {
 impl NetworkObserver {
     #[maybe_async::maybe_async]
     pub async fn current_state(&self) -> NetworkState {
-        let mut current_state = NetworkState::new();
-        // update current state
-        if self.config.observe_all_interfaces {
-            current_state.all_interfaces = Some(Interfaces::new(netdev::get_interfaces()));
-        }
-        // get default route
-        if self.config.observe_default_route {
-            current_state.default_route = routes::get_default_route().await;
-        }
-        // get all routes
-        if self.config.observe_all_routes {
-            current_state.all_routes = routes::get_all_routes().await;
-        }
-        // get public address
-        if self.config.observe_public_address {
-            if let Ok(response) = public_ip_address::perform_cached_lookup_with(
-                vec![
-                    (LookupProvider::MyIpCom, None),
-                    (LookupProvider::GetJsonIp, None),
-                    (LookupProvider::Ipify, None),
-                    (LookupProvider::IpInfo, None),
-                ],
-                None,
-                Some(10),
-                false,
-            )
-            .await
-            {
-                current_state.public_address = Some(response.ip);
-            } else {
-                current_state.public_address = None;
-                warn!("Failed to get public IP address");
-            }
+        match self.probe().await {
+            Ok(state) => state,
+            Err(e) => NetworkState::unknown(&self.config, &e.to_string()),
         }
-        current_state
     }
 
+    /// Probe the state, failing only if the configured network namespace cannot be entered.
+    #[maybe_async::maybe_async]
+    async fn probe(&self) -> error::Result<NetworkState> {
+        let previous = self
+            .last_state
+            .all_interfaces
+            .observed()
+            .and_then(Interfaces::statistics);
+        let Some(path) = &self.config.netns else {
+            return Ok(probe_state(&self.config, previous).await);
+        };
+        netns::probe_state_in(path, &self.config, previous)
+            .await
+            .inspect_err(|e| {
+                warn!(
+                    "Failed to observe network namespace {}: {}",
+                    path.display(),
+                    e
+                )
+            })
+    }
+
+    /// Report the change since the last call.
+    ///
+    /// If the network namespace cannot be entered, nothing was observed and
+    /// [`Probe::Namespace`] is reported as failed.
     #[maybe_async::maybe_async]
     pub async fn state_change(&mut self) -> NetworkChange {
-        let current_state = self.current_state().await;
-        let state_changed = self.last_state.compare(&current_state, &self.config);
+        self.try_state_change()
+            .await
+            .unwrap_or(NetworkChange::ProbeFailed(Probe::Namespace))
+    }
+
+    /// Report the change since the last call, failing if the network namespace cannot be
+    /// entered.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn try_state_change(&mut self) -> error::Result<NetworkChange> {
+        let current_state = self.probe().await?;
+        let state_changed = match self.last_state.compare(&current_state, &self.config) {
+            // report failed probes only when nothing else changed
+            NetworkChange::None => current_state
+                .failed_probes()
+                .first()
+                .map_or(NetworkChange::None, |probe| {
+                    NetworkChange::ProbeFailed(*probe)
+                }),
+            NetworkChange::JoinedUnknownNetwork(id) => match self.known_networks.label(&id) {
+                Some(label) => NetworkChange::JoinedKnownNetwork(label.to_string()),
+                None => NetworkChange::JoinedUnknownNetwork(id),
+            },
+            change => change,
+        };
 
-        if state_changed != NetworkChange::None {
+        if state_changed.is_change() {
             // call on_change callback
-            if let Some(callback) = self.config.on_change {
+            if let Some(OnChange(callback)) = self.config.on_change {
                 callback(&state_changed, &self.last_state, &current_state);
             }
             //update state
-            self.last_state = current_state;
+            self.last_state.update(current_state);
+        } else {
+            // learn components that were unknown, without resetting the expire time
+            let last_update = self.last_state.last_update;
+            self.last_state.update(current_state);
+            self.last_state.last_update = last_update;
         }
-        state_changed
+        self.autosave(&state_changed);
+        Ok(state_changed)
     }
 
     #[maybe_async::maybe_async]
     pub async fn state_did_change(&mut self) -> bool {
-        self.state_change().await != NetworkChange::None
+        self.state_change().await.is_change()
+    }
+
+    /// Id of the network observed last, if network ids are observed.
+    pub fn network_id(&self) -> Option<&NetworkId> {
+        self.last_state.network_id.observed()
+    }
+
+    pub fn known_networks(&self) -> &KnownNetworks {
+        &self.known_networks
+    }
+
+    pub fn known_networks_mut(&mut self) -> &mut KnownNetworks {
+        &mut self.known_networks
+    }
+
+    /// Label the network observed last, returning its id.
+    pub fn label_current_network(&mut self, label: impl Into<String>) -> Option<NetworkId> {
+        let id = self.network_id()?.clone();
+        self.known_networks.insert(id.clone(), label);
+        Some(id)
+    }
+
+    /// Persist the last observed state and the known networks to the configured stores.
+    ///
+    /// Fails without writing anything if the persisted state failed to load.
+    pub fn checkpoint(&mut self) -> error::Result<()> {
+        if let Some(e) = &self.load_error {
+            return Err(error::Error::StateNotLoaded(e.clone()));
+        }
+        trace!("Persisting state");
+        self.last_state.save_with(&self.config)?;
+        if self.config.observe_network_id {
+            self.known_networks.save_with(&self.config)?;
+        }
+        self.last_checkpoint = Instant::now();
+        Ok(())
+    }
+
+    /// Persist the state if persistence is enabled and stop observing.
+    ///
+    /// Unlike dropping the observer, errors are returned to the caller.
+    #[maybe_async::maybe_async]
+    pub async fn close(mut self) -> error::Result<()> {
+        self.closed = true;
+        if self.config.persist {
+            self.checkpoint()?;
+        }
+        Ok(())
     }
+
+    fn autosave(&mut self, change: &NetworkChange) {
+        if !self.config.persist || self.load_error.is_some() {
+            return;
+        }
+        let due = match self.config.autosave {
+            Autosave::Off => false,
+            Autosave::OnChange => change.is_change(),
+            Autosave::Interval(secs) => self.last_checkpoint.elapsed().as_secs() >= secs,
+        };
+        if due {
+            if let Err(e) = self.checkpoint() {
+                warn!("Failed to persist state: {}", e);
+            }
+        }
+    }
+}
+
+/// Unknown for the components read from sysfs or files of the host, which do not follow the
+/// namespace of the probing thread.
+fn unsupported_in_namespace<T>(component: &str) -> Observation<T> {
+    Observation::Unknown(
+        error::Error::Unsupported(format!("{} in network namespaces", component)).to_string(),
+    )
+}
+
+/// Probe the components enabled in `config`, in the network namespace of the calling thread.
+#[maybe_async::maybe_async]
+async fn probe_state(config: &ObserverConfig, previous: Option<&Statistics>) -> NetworkState {
+    let mut current_state = NetworkState::new();
+    // drop the IPv6 addresses that are not compared
+    let ipv6_filter = config.ipv6_address_filter;
+    let unstable = match ipv6_filter {
+        Ipv6AddressFilter::Stable => match addresses::get_unstable_ipv6_addresses().await {
+            Ok(unstable) => unstable,
+            Err(e) => {
+                warn!("Failed to get IPv6 address flags: {}", e);
+                HashSet::new()
+            }
+        },
+        _ => HashSet::new(),
+    };
+    // netdev reads some fields from files that show the namespace of the process
+    let in_namespace = config.netns.is_some();
+    if let Observation::Observed(interface) = &mut current_state.default_interface {
+        ipv6_filter.apply(interface, &unstable);
+        if in_namespace {
+            network_interfaces::clear_host_fields(interface);
+        }
+    }
+    // update current state
+    if config.observe_all_interfaces || config.statistics_thresholds.is_some() {
+        let mut interfaces = netdev::get_interfaces();
+        for interface in interfaces.iter_mut() {
+            ipv6_filter.apply(interface, &unstable);
+            if in_namespace {
+                network_interfaces::clear_host_fields(interface);
+            }
+        }
+        // read over netlink, in the observed namespace
+        let (mut links, counters): (HashMap<_, _>, HashMap<_, _>) = match link::get_links().await {
+            Ok(links) => links
+                .into_iter()
+                .filter(|(name, _)| interfaces.iter().any(|interface| &interface.name == name))
+                .map(|(name, link)| ((name.clone(), link.properties), (name, link.counters)))
+                .unzip(),
+            // link properties are only read on Linux
+            Err(error::Error::Unsupported(_)) => Default::default(),
+            Err(e) => {
+                warn!("Failed to get links: {}", e);
+                Default::default()
+            }
+        };
+        // sysfs shows the namespace it was mounted in
+        if config.netns.is_none() {
+            for (name, link) in links.iter_mut() {
+                if let Some(sysfs) = link::LinkProperties::read(name) {
+                    link.speed = sysfs.speed;
+                    link.duplex = sysfs.duplex;
+                }
+            }
+        }
+        let ignored = |link: &link::LinkProperties| {
+            link.kind
+                .as_ref()
+                .is_some_and(|kind| config.ignored_link_kinds.contains(kind))
+        };
+        interfaces.retain(|interface| !links.get(&interface.name).is_some_and(ignored));
+        links.retain(|_, link| !ignored(link));
+        let statistics = config.statistics_thresholds.as_ref().map(|_| {
+            let statistics = Statistics::new(
+                counters
+                    .into_iter()
+                    .filter_map(|(name, counters)| Some((name, counters?)))
+                    .filter(|(name, _)| links.contains_key(name))
+                    .collect(),
+            );
+            // rates since the previous call
+            match previous {
+                Some(previous) => statistics.with_rates_since(previous),
+                None => statistics,
+            }
+        });
+        let mut all_interfaces = Interfaces::new(interfaces).with_links(links);
+        if let Some(statistics) = statistics {
+            all_interfaces = all_interfaces.with_statistics(statistics);
+        }
+        current_state.all_interfaces = Observation::Observed(all_interfaces);
+    }
+    // get default route
+    if config.observe_default_route {
+        match routes::get_default_routes().await {
+            Ok(default_routes) => {
+                current_state.default_route =
+                    Observation::from_option(default_routes.preferred().cloned());
+                current_state.default_routes = Observation::Observed(default_routes);
+            }
+            Err(e) => {
+                warn!("Failed to get default route: {}", e);
+                current_state.default_route = Observation::Unknown(e.to_string());
+                current_state.default_routes = Observation::Unknown(e.to_string());
+            }
+        };
+    }
+    // get all routes
+    if config.observe_all_routes || !config.watched_destinations.is_empty() {
+        current_state.all_routes = match routes::get_all_routes().await {
+            Ok(routes) => Observation::Observed(routes.into()),
+            Err(e) => {
+                warn!("Failed to get all routes: {}", e);
+                Observation::Unknown(e.to_string())
+            }
+        };
+    }
+    // get policy routing rules
+    if config.observe_routing_rules {
+        current_state.routing_rules = match rules::get_routing_rules().await {
+            Ok(rules) => Observation::Observed(rules),
+            Err(e) => {
+                warn!("Failed to get routing rules: {}", e);
+                Observation::Unknown(e.to_string())
+            }
+        };
+    }
+    // get public address
+    if config.observe_public_address {
+        // the cache is not per namespace
+        let address = match config.netns {
+            Some(_) => public_address::lookup_public_address().await,
+            None => public_address::get_public_address().await,
+        };
+        current_state.public_address = match address {
+            Ok(address) => Observation::Observed(address),
+            Err(e) => {
+                warn!("Failed to get public IP address: {}", e);
+                Observation::Unknown(e.to_string())
+            }
+        };
+    }
+    // get the gateway's link-layer address
+    if config.observe_gateway_mac {
+        current_state.gateway_mac = gateway_mac(&current_state.default_route).await;
+    }
+    // get wireless associations
+    if config.observe_wireless {
+        current_state.wireless = if config.netns.is_some() {
+            unsupported_in_namespace("wireless interfaces")
+        } else {
+            match wireless::get_wireless_interfaces().await {
+                Ok(interfaces) => Observation::Observed(interfaces),
+                Err(e) => {
+                    warn!("Failed to get wireless interfaces: {}", e);
+                    Observation::Unknown(e.to_string())
+                }
+            }
+        };
+    }
+    // detect tunnelled routes
+    if config.observe_vpn {
+        current_state.vpns = match vpn::get_vpns().await {
+            Ok(vpns) => Observation::Observed(vpns),
+            Err(e) => {
+                warn!("Failed to detect VPNs: {}", e);
+                Observation::Unknown(e.to_string())
+            }
+        };
+    }
+    // fingerprint the network
+    if config.observe_network_id {
+        current_state.network_id =
+            if current_state.default_route.is_unknown() || current_state.gateway_mac.is_unknown() {
+                Observation::Unknown("network id components unknown".to_string())
+            } else {
+                Observation::from_option(NetworkId::from_state(&current_state))
+            };
+    }
+    // get DNS configuration
+    if config.observe_dns_config {
+        current_state.dns_config = if config.netns.is_some() {
+            unsupported_in_namespace("DNS configuration")
+        } else {
+            match dns::get_dns_config() {
+                Ok(dns_config) => Observation::from_option(dns_config),
+                Err(e) => {
+                    warn!("Failed to get DNS configuration: {}", e);
+                    Observation::Unknown(e.to_string())
+                }
+            }
+        };
+    }
+    // check connectivity
+    if let Some(check) = &config.connectivity_check {
+        current_state.connectivity = match connectivity::check_connectivity(check).await {
+            Ok(connectivity) => Observation::Observed(connectivity),
+            Err(e) => {
+                warn!("Failed to check connectivity: {}", e);
+                Observation::Unknown(e.to_string())
+            }
+        };
+    }
+    current_state
+}
+
+#[maybe_async::maybe_async]
+async fn gateway_mac(default_route: &Observation<Route>) -> Observation<MacAddr> {
+    let default_route = match default_route {
+        Observation::NotObserved => match routes::get_default_route().await {
+            Ok(route) => Observation::from_option(route),
+            Err(e) => Observation::Unknown(e.to_string()),
+        },
+        route => route.clone(),
+    };
+    let route = match default_route {
+        Observation::Observed(route) => route,
+        Observation::Unknown(e) => return Observation::Unknown(e),
+        _ => return Observation::Absent,
+    };
+    // the first next hop of a multipath route
+    let (Some(gateway), ifindex) = route.hops()[0] else {
+        return Observation::Absent;
+    };
+    match neighbours::get_neighbour_mac(gateway, ifindex).await {
+        Ok(Some(mac)) => Observation::Observed(mac),
+        Ok(None) => Observation::Unknown(format!("{} not in neighbour table", gateway)),
+        Err(e) => {
+            warn!("Failed to get gateway link-layer address: {}", e);
+            Observation::Unknown(e.to_string())
+        }
+    }
+}
+
+/// Netlink handles spawn tokio tasks, so blocking probes run on their own runtime.
+#[cfg(feature = "blocking")]
+pub(crate) fn block_on<T>(
+    future: impl std::future::Future<Output = error::Result<T>>,
+) -> error::Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?
+        .block_on(future)
 }
 
 impl Drop for NetworkObserver {
     fn drop(&mut self) {
-        if self.config.persist {
-            trace!("Persisting state");
-            _ = self.last_state.save();
+        if self.config.persist && !self.closed && self.load_error.is_none() {
+            if let Err(e) = self.checkpoint() {
+                warn!("Failed to persist state: {}", e);
+            }
         }
     }
 }
@@ -129,4 +612,128 @@ mod tests {
         let mut observer = NetworkObserver::new(config);
         assert_eq!(observer.state_change().await, NetworkChange::Expired);
     }
+
+    #[test]
+    fn probe_failure_is_not_a_change() {
+        assert!(!NetworkChange::None.is_change());
+        assert!(!NetworkChange::ProbeFailed(Probe::PublicAddress).is_change());
+        assert!(NetworkChange::PublicAddress.is_change());
+    }
+
+    #[test]
+    fn persist_to_store() {
+        let config = ObserverConfig::default()
+            .enable_persist(true)
+            .set_store(state_store::MemoryStore::new());
+        drop(NetworkObserver::new(config.clone()));
+        assert!(NetworkState::load_with(&config).is_ok());
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn autosave_on_change() {
+        let mut expired = NetworkState::new();
+        expired.last_update -= Duration::from_secs(DEFAULT_EXPIRE_TIME);
+        let config = ObserverConfig::default()
+            .enable_persist(true)
+            .set_store(state_store::MemoryStore::new())
+            .set_autosave(Autosave::OnChange);
+        expired.save_with(&config).unwrap();
+
+        let mut observer = NetworkObserver::new(config.clone());
+        assert_eq!(observer.state_change().await, NetworkChange::Expired);
+        assert_eq!(
+            NetworkState::load_with(&config).unwrap(),
+            observer.last_state
+        );
+        observer.close().await.unwrap();
+    }
+
+    #[test]
+    fn persist_known_networks() {
+        let store = state_store::MemoryStore::new();
+        let config = || {
+            ObserverConfig::default()
+                .enable_persist(true)
+                .enable_observe_network_id(true)
+                .set_store(state_store::MemoryStore::new())
+                .set_known_networks_store(store.clone())
+        };
+        let mut state = NetworkState::new();
+        state.default_interface = Observation::Observed(netdev::Interface::dummy());
+        let id = NetworkId::from_state(&state).unwrap();
+
+        let mut observer = NetworkObserver::new(config());
+        observer.known_networks_mut().insert(id.clone(), "home");
+        drop(observer);
+
+        let observer = NetworkObserver::new(config());
+        assert_eq!(observer.known_networks().label(&id), Some("home"));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn close_returns_errors() {
+        let config =
+            ObserverConfig::default()
+                .enable_persist(true)
+                .set_store(state_store::FileStore::new(
+                    std::env::temp_dir().join("missing-dir").join("state.cache"),
+                ));
+        let observer = NetworkObserver::new(config);
+        assert!(observer.close().await.is_err());
+    }
+
+    #[test]
+    fn tampered_state_is_not_overwritten() {
+        let store = state_store::MemoryStore::new();
+        let config = ObserverConfig::default()
+            .enable_persist(true)
+            .set_store(store.clone())
+            .set_integrity(integrity::Integrity::Checksum);
+        NetworkState::new().save_with(&config).unwrap();
+        let mut data = store.load().unwrap();
+        data[0] ^= 1;
+        store.save(&data).unwrap();
+
+        assert!(matches!(
+            NetworkObserver::try_new(config.clone()),
+            Err(error::Error::IntegrityError(_))
+        ));
+        let mut observer = NetworkObserver::new(config);
+        assert!(observer.load_error().is_some());
+        assert!(matches!(
+            observer.checkpoint(),
+            Err(error::Error::StateNotLoaded(_))
+        ));
+        drop(observer);
+        assert_eq!(store.load().unwrap(), data);
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn missing_key_is_an_error() {
+        use encryption::EncryptionKey;
+
+        let store = state_store::MemoryStore::new();
+        let config = ObserverConfig::default()
+            .enable_persist(true)
+            .set_store(store.clone());
+        NetworkState::new()
+            .save_with(
+                &config
+                    .clone()
+                    .set_encryption_key(EncryptionKey::Bytes(b"secret".to_vec())),
+            )
+            .unwrap();
+        let data = store.load().unwrap();
+
+        let config = config.set_encryption_key(EncryptionKey::Env(
+            "NETWORK_CHANGED_TEST_MISSING_OBSERVER_KEY".to_string(),
+        ));
+        assert!(matches!(
+            NetworkObserver::try_new(config.clone()),
+            Err(error::Error::EncryptionError(_))
+        ));
+        drop(NetworkObserver::new(config));
+        assert_eq!(store.load().unwrap(), data);
+    }
 }
@@ -0,0 +1,324 @@
+use crate::{error::Result, statistics::InterfaceCounters};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[cfg(target_os = "linux")]
+use crate::error::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplex {
+    Full,
+    Half,
+}
+
+/// Operational state as defined by RFC 2863.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OperState {
+    #[default]
+    Unknown,
+    NotPresent,
+    Down,
+    LowerLayerDown,
+    Testing,
+    Dormant,
+    Up,
+}
+
+impl OperState {
+    pub fn parse(operstate: &str) -> Self {
+        match operstate.trim() {
+            "notpresent" => Self::NotPresent,
+            "down" => Self::Down,
+            "lowerlayerdown" => Self::LowerLayerDown,
+            "testing" => Self::Testing,
+            "dormant" => Self::Dormant,
+            "up" => Self::Up,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Link properties that `netdev` does not report.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkProperties {
+    /// `None` when the interface is administratively down.
+    pub carrier: Option<bool>,
+    /// Speed in Mbit/s, `None` when unknown.
+    pub speed: Option<u32>,
+    pub duplex: Option<Duplex>,
+    pub operstate: OperState,
+    /// Name of the bridge or bond the interface is enslaved to.
+    #[serde(default)]
+    pub master: Option<String>,
+    /// Index of the peer of a veth or the parent of a VLAN, possibly in another namespace.
+    #[serde(default)]
+    pub peer_ifindex: Option<u32>,
+    /// Link kind such as `veth`, `bridge` or `vxlan`, `None` for physical interfaces.
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+impl LinkProperties {
+    /// Parse the contents of the `carrier`, `speed`, `duplex` and `operstate` sysfs attributes.
+    pub fn parse(
+        carrier: Option<&str>,
+        speed: Option<&str>,
+        duplex: Option<&str>,
+        operstate: Option<&str>,
+    ) -> Self {
+        Self {
+            carrier: carrier.and_then(|carrier| match carrier.trim() {
+                "1" => Some(true),
+                "0" => Some(false),
+                _ => None,
+            }),
+            // -1 when unknown
+            speed: speed
+                .and_then(|speed| speed.trim().parse::<i64>().ok())
+                .and_then(|speed| u32::try_from(speed).ok()),
+            duplex: duplex.and_then(|duplex| match duplex.trim() {
+                "full" => Some(Duplex::Full),
+                "half" => Some(Duplex::Half),
+                _ => None,
+            }),
+            operstate: operstate.map(OperState::parse).unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// Read the properties of the named interface from `/sys/class/net`.
+    #[cfg(target_os = "linux")]
+    pub fn read(name: &str) -> Option<Self> {
+        let dir = std::path::Path::new("/sys/class/net").join(name);
+        if !dir.exists() {
+            return None;
+        }
+        // reading carrier, speed or duplex fails while the link is down
+        let read = |attribute: &str| std::fs::read_to_string(dir.join(attribute)).ok();
+        let index = |attribute: &str| read(attribute)?.trim().parse::<u32>().ok();
+        let mut link = Self::parse(
+            read("carrier").as_deref(),
+            read("speed").as_deref(),
+            read("duplex").as_deref(),
+            read("operstate").as_deref(),
+        );
+        link.master = std::fs::read_link(dir.join("master"))
+            .ok()
+            .and_then(|master| Some(master.file_name()?.to_string_lossy().into_owned()));
+        // iflink is the own index unless the link is paired with another
+        link.peer_ifindex = index("iflink").filter(|iflink| Some(*iflink) != index("ifindex"));
+        Some(link)
+    }
+
+    /// Read the properties of the named interface, not supported on this platform.
+    #[cfg(not(target_os = "linux"))]
+    pub fn read(_name: &str) -> Option<Self> {
+        None
+    }
+}
+
+/// Link properties and traffic counters read over netlink, in the namespace of the calling
+/// thread. Speed and duplex are only in sysfs and left `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Link {
+    pub properties: LinkProperties,
+    pub counters: Option<InterfaceCounters>,
+}
+
+#[cfg(target_os = "linux")]
+impl From<netlink_packet_route::link::State> for OperState {
+    fn from(state: netlink_packet_route::link::State) -> Self {
+        use netlink_packet_route::link::State;
+
+        match state {
+            State::NotPresent => Self::NotPresent,
+            State::Down => Self::Down,
+            State::LowerLayerDown => Self::LowerLayerDown,
+            State::Testing => Self::Testing,
+            State::Dormant => Self::Dormant,
+            State::Up => Self::Up,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The name, link and index of the master of a link.
+#[cfg(target_os = "linux")]
+fn link_from_message(
+    message: netlink_packet_route::link::LinkMessage,
+) -> Option<(String, Link, Option<u32>)> {
+    use netlink_packet_route::link::{LinkAttribute, LinkFlags, LinkInfo};
+
+    let index = message.header.index;
+    // carrier is not reported while the link is administratively down
+    let up = message.header.flags.contains(LinkFlags::Up);
+    let mut name = None;
+    let mut master = None;
+    let mut link = Link::default();
+    for attribute in message.attributes {
+        match attribute {
+            LinkAttribute::IfName(n) => name = Some(n),
+            LinkAttribute::Carrier(carrier) => link.properties.carrier = up.then_some(carrier != 0),
+            LinkAttribute::OperState(state) => link.properties.operstate = state.into(),
+            LinkAttribute::Controller(controller) => master = Some(controller),
+            // the own index unless the link is paired with another
+            LinkAttribute::Link(peer) => {
+                link.properties.peer_ifindex = Some(peer).filter(|peer| *peer != index)
+            }
+            LinkAttribute::LinkInfo(infos) => {
+                link.properties.kind = infos.into_iter().find_map(|info| match info {
+                    LinkInfo::Kind(kind) => Some(kind.to_string()),
+                    _ => None,
+                })
+            }
+            LinkAttribute::Stats64(stats) => {
+                link.counters = Some(InterfaceCounters {
+                    rx_bytes: stats.rx_bytes,
+                    rx_packets: stats.rx_packets,
+                    rx_errors: stats.rx_errors,
+                    rx_dropped: stats.rx_dropped,
+                    tx_bytes: stats.tx_bytes,
+                    tx_packets: stats.tx_packets,
+                    tx_errors: stats.tx_errors,
+                    tx_dropped: stats.tx_dropped,
+                })
+            }
+            _ => {}
+        }
+    }
+    Some((name?, link, master))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) async fn links() -> Result<HashMap<String, Link>> {
+    use futures::TryStreamExt;
+
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+    let mut messages = handle.link().get().execute();
+    let mut names = HashMap::new();
+    let mut links = Vec::new();
+    while let Some(message) = messages
+        .try_next()
+        .await
+        .map_err(|e| Error::NetlinkError(e.to_string()))?
+    {
+        let index = message.header.index;
+        if let Some((name, link, master)) = link_from_message(message) {
+            names.insert(index, name.clone());
+            links.push((name, link, master));
+        }
+    }
+    let links: HashMap<String, Link> = links
+        .into_iter()
+        .map(|(name, mut link, master)| {
+            link.properties.master = master.and_then(|master| names.get(&master).cloned());
+            (name, link)
+        })
+        .collect();
+    debug!("Links: {:?}", links);
+    Ok(links)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) async fn links() -> Result<HashMap<String, Link>> {
+    debug!("Links are not supported");
+    Err(crate::error::Error::Unsupported("links".to_string()))
+}
+
+/// Get the properties and counters of all links by interface name.
+#[maybe_async::async_impl]
+pub async fn get_links() -> Result<HashMap<String, Link>> {
+    links().await
+}
+
+/// Get the properties and counters of all links by interface name.
+#[maybe_async::sync_impl]
+pub fn get_links() -> Result<HashMap<String, Link>> {
+    crate::block_on(links())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let link = LinkProperties::parse(Some("1\n"), Some("1000\n"), Some("full\n"), Some("up\n"));
+        assert_eq!(
+            link,
+            LinkProperties {
+                carrier: Some(true),
+                speed: Some(1000),
+                duplex: Some(Duplex::Full),
+                operstate: OperState::Up,
+                ..Default::default()
+            }
+        );
+
+        let down = LinkProperties::parse(None, Some("-1\n"), Some("unknown\n"), Some("down\n"));
+        assert_eq!(
+            down,
+            LinkProperties {
+                carrier: None,
+                speed: None,
+                duplex: None,
+                operstate: OperState::Down,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn from_message() {
+        use netlink_packet_route::link::{
+            InfoKind, LinkAttribute, LinkFlags, LinkInfo, LinkMessage, State, Stats64,
+        };
+
+        let mut stats = Stats64::default();
+        stats.rx_bytes = 1000;
+        stats.tx_bytes = 2000;
+        let mut message = LinkMessage::default();
+        message.header.index = 5;
+        message.header.flags = LinkFlags::Up;
+        message.attributes = vec![
+            LinkAttribute::IfName("veth0".to_string()),
+            LinkAttribute::Carrier(1),
+            LinkAttribute::OperState(State::Up),
+            LinkAttribute::Controller(3),
+            LinkAttribute::Link(4),
+            LinkAttribute::LinkInfo(vec![LinkInfo::Kind(InfoKind::Veth)]),
+            LinkAttribute::Stats64(stats),
+        ];
+        let (name, link, master) = link_from_message(message.clone()).unwrap();
+        assert_eq!(name, "veth0");
+        assert_eq!(master, Some(3));
+        assert_eq!(
+            link,
+            Link {
+                properties: LinkProperties {
+                    carrier: Some(true),
+                    operstate: OperState::Up,
+                    peer_ifindex: Some(4),
+                    kind: Some("veth".to_string()),
+                    ..Default::default()
+                },
+                counters: Some(InterfaceCounters {
+                    rx_bytes: 1000,
+                    tx_bytes: 2000,
+                    ..Default::default()
+                }),
+            }
+        );
+
+        // administratively down and not paired
+        message.header.flags = LinkFlags::empty();
+        message.attributes[4] = LinkAttribute::Link(5);
+        let (_, link, _) = link_from_message(message).unwrap();
+        assert_eq!(link.properties.carrier, None);
+        assert_eq!(link.properties.peer_ifindex, None);
+    }
+}
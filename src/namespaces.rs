@@ -0,0 +1,253 @@
+use crate::{NetworkChange, NetworkObserver, ObserverConfig};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+/// Directory of the namespaces named by `ip netns add`.
+pub const NETNS_DIR: &str = "/var/run/netns";
+
+/// Identifies an observed network namespace.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NamespaceId {
+    /// A namespace in the netns directory, by file name.
+    Named(String),
+    /// The namespace of a process, by the first process seen in it.
+    Process(u32),
+}
+
+impl fmt::Display for NamespaceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "{}", name),
+            Self::Process(pid) => write!(f, "pid {}", pid),
+        }
+    }
+}
+
+/// A change in one of the namespaces of a [`NetworkObserverSet`].
+#[derive(Debug, PartialEq)]
+pub enum NamespaceChange {
+    Created(NamespaceId),
+    Destroyed(NamespaceId),
+    Changed(NamespaceId, NetworkChange),
+    /// The namespace could not be entered, reported again only after it was observed.
+    Failed(NamespaceId, String),
+}
+
+#[derive(Debug)]
+struct Namespace {
+    inode: u64,
+    observer: NetworkObserver,
+    failed: bool,
+}
+
+impl Namespace {
+    /// Report the change in the namespace, or its failure unless already reported.
+    #[maybe_async::maybe_async]
+    async fn state_change(&mut self, id: &NamespaceId) -> Option<NamespaceChange> {
+        match self.observer.try_state_change().await {
+            Ok(change) => {
+                self.failed = false;
+                change
+                    .is_change()
+                    .then(|| NamespaceChange::Changed(id.clone(), change))
+            }
+            Err(e) if !self.failed => {
+                self.failed = true;
+                Some(NamespaceChange::Failed(id.clone(), e.to_string()))
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+/// Keeps a [`NetworkObserver`] for each discovered network namespace, Linux only.
+#[derive(Debug)]
+pub struct NetworkObserverSet {
+    config: ObserverConfig,
+    netns_dir: PathBuf,
+    process_namespaces: bool,
+    namespaces: BTreeMap<NamespaceId, Namespace>,
+}
+
+impl NetworkObserverSet {
+    /// Observe each namespace with a copy of `config`, without persistence.
+    pub fn new(config: ObserverConfig) -> Self {
+        Self {
+            config: config.enable_persist(false),
+            netns_dir: PathBuf::from(NETNS_DIR),
+            process_namespaces: false,
+            namespaces: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_netns_dir(mut self, netns_dir: impl Into<PathBuf>) -> Self {
+        self.netns_dir = netns_dir.into();
+        self
+    }
+
+    /// Also observe the namespaces of processes, such as containers, other than the own one.
+    pub fn with_process_namespaces(mut self, process_namespaces: bool) -> Self {
+        self.process_namespaces = process_namespaces;
+        self
+    }
+
+    pub fn namespaces(&self) -> impl Iterator<Item = &NamespaceId> {
+        self.namespaces.keys()
+    }
+
+    pub fn get(&self, id: &NamespaceId) -> Option<&NetworkObserver> {
+        self.namespaces.get(id).map(|namespace| &namespace.observer)
+    }
+
+    /// Discover namespaces and report the changes in all of them since the last call.
+    ///
+    /// A namespace whose file now refers to another namespace is destroyed and created again.
+    #[maybe_async::maybe_async]
+    pub async fn state_changes(&mut self) -> Vec<NamespaceChange> {
+        let discovered = self.discover();
+        let mut changes = Vec::new();
+        let destroyed: Vec<NamespaceId> = self
+            .namespaces
+            .iter()
+            .filter(|(id, namespace)| {
+                discovered.get(*id).map(|(_, inode)| *inode) != Some(namespace.inode)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in destroyed {
+            self.namespaces.remove(&id);
+            changes.push(NamespaceChange::Destroyed(id));
+        }
+        for (id, namespace) in self.namespaces.iter_mut() {
+            changes.extend(namespace.state_change(id).await);
+        }
+        for (id, (path, inode)) in discovered {
+            if self.namespaces.contains_key(&id) {
+                continue;
+            }
+            let mut namespace = Namespace {
+                inode,
+                observer: NetworkObserver::new(self.config.clone().set_netns(path)),
+                failed: false,
+            };
+            changes.push(NamespaceChange::Created(id.clone()));
+            // the first observation is the baseline
+            let failed = match namespace.state_change(&id).await {
+                Some(failed @ NamespaceChange::Failed(..)) => Some(failed),
+                _ => None,
+            };
+            self.namespaces.insert(id, namespace);
+            changes.extend(failed);
+        }
+        changes
+    }
+
+    /// Paths and inodes of the namespaces to observe.
+    #[cfg(target_os = "linux")]
+    fn discover(&self) -> BTreeMap<NamespaceId, (PathBuf, u64)> {
+        use std::{collections::HashSet, os::unix::fs::MetadataExt, path::Path};
+
+        // other files are not namespaces, even in the netns dir
+        let inode = |path: &Path| {
+            Some(std::fs::metadata(path).ok()?.ino())
+                .filter(|_| crate::netns::is_network_namespace(path))
+        };
+        let mut discovered = BTreeMap::new();
+        if let Ok(entries) = std::fs::read_dir(&self.netns_dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if let Some(inode) = inode(&path) {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    discovered.insert(NamespaceId::Named(name), (path, inode));
+                }
+            }
+        }
+        if !self.process_namespaces {
+            return discovered;
+        }
+        let mut seen: HashSet<u64> = discovered.values().map(|(_, inode)| *inode).collect();
+        seen.extend(inode(Path::new("/proc/self/ns/net")));
+        // processes already identifying a namespace keep doing so
+        let mut pids: Vec<u32> = self
+            .namespaces
+            .keys()
+            .filter_map(|id| match id {
+                NamespaceId::Process(pid) => Some(*pid),
+                _ => None,
+            })
+            .collect();
+        let mut processes: Vec<u32> = std::fs::read_dir("/proc")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .collect();
+        processes.sort();
+        pids.extend(processes);
+        for pid in pids {
+            let path = PathBuf::from(format!("/proc/{}/ns/net", pid));
+            if let Some(inode) = inode(&path) {
+                if seen.insert(inode) {
+                    discovered.insert(NamespaceId::Process(pid), (path, inode));
+                }
+            }
+        }
+        discovered
+    }
+
+    /// Paths and inodes of the namespaces to observe, none on this platform.
+    #[cfg(not(target_os = "linux"))]
+    fn discover(&self) -> BTreeMap<NamespaceId, (PathBuf, u64)> {
+        BTreeMap::new()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_namespace_files() {
+        assert!(crate::netns::is_network_namespace(std::path::Path::new(
+            "/proc/self/ns/net"
+        )));
+        assert!(!crate::netns::is_network_namespace(std::path::Path::new(
+            "/proc/self/ns/uts"
+        )));
+        assert!(!crate::netns::is_network_namespace(std::path::Path::new(
+            "/proc/self/status"
+        )));
+    }
+
+    #[maybe_async::test(feature = "blocking", async(not(feature = "blocking"), tokio::test))]
+    async fn created_and_destroyed() {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{}-netns",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        // not a namespace
+        std::fs::write(dir.join("a"), b"").unwrap();
+        std::os::unix::fs::symlink("/proc/self/ns/net", dir.join("b")).unwrap();
+
+        let mut observers = NetworkObserverSet::new(ObserverConfig::default()).with_netns_dir(&dir);
+        let named = |name: &str| NamespaceId::Named(name.to_string());
+        let changes = observers.state_changes().await;
+        assert_eq!(changes[0], NamespaceChange::Created(named("b")));
+        // entering a namespace takes CAP_SYS_ADMIN, without it the failure follows once
+        assert!(changes.len() <= 2);
+        assert!(changes[1..]
+            .iter()
+            .all(|change| matches!(change, NamespaceChange::Failed(id, _) if *id == named("b"))));
+        assert_eq!(observers.state_changes().await, vec![]);
+
+        std::fs::remove_file(dir.join("b")).unwrap();
+        assert_eq!(
+            observers.state_changes().await,
+            vec![NamespaceChange::Destroyed(named("b"))]
+        );
+        assert_eq!(observers.namespaces().count(), 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use crate::error::Result;
+use log::debug;
+pub use netdev::mac::MacAddr;
+use std::net::{IpAddr, Ipv4Addr};
+
+#[cfg(target_os = "linux")]
+use crate::{error::Error, routes};
+
+/// Parse the IPv4 neighbour table in `/proc/net/arp` format, skipping incomplete entries.
+pub fn parse_arp_table(contents: &str) -> Vec<(Ipv4Addr, MacAddr, String)> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let [address, _, flags, mac, _, device] = columns[..] else {
+                return None;
+            };
+            // ATF_COM, the entry is complete
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            if flags & 0x2 == 0 {
+                return None;
+            }
+            let mac: MacAddr = mac.parse().ok()?;
+            if mac == MacAddr::zero() {
+                return None;
+            }
+            Some((address.parse().ok()?, mac, device.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn ipv4_neighbour(address: Ipv4Addr, ifindex: Option<u32>) -> Result<Option<MacAddr>> {
+    // /proc/net follows the namespace of the process, thread-self that of the calling thread
+    let contents = std::fs::read_to_string("/proc/thread-self/net/arp")?;
+    Ok(parse_arp_table(&contents)
+        .into_iter()
+        .filter(|(a, _, _)| *a == address)
+        .find(|(_, _, device)| {
+            ifindex.is_none_or(|ifindex| routes::interface_index(device) == Some(ifindex))
+        })
+        .map(|(_, mac, _)| mac))
+}
+
+#[cfg(target_os = "linux")]
+async fn ipv6_neighbour(
+    address: std::net::Ipv6Addr,
+    ifindex: Option<u32>,
+) -> Result<Option<MacAddr>> {
+    use futures::TryStreamExt;
+    use netlink_packet_route::neighbour::{NeighbourAddress, NeighbourAttribute};
+
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+    let mut neighbours = handle
+        .neighbours()
+        .get()
+        .set_family(rtnetlink::IpVersion::V6)
+        .execute();
+    while let Some(message) = neighbours
+        .try_next()
+        .await
+        .map_err(|e| Error::NetlinkError(e.to_string()))?
+    {
+        if ifindex.is_some_and(|ifindex| ifindex != message.header.ifindex) {
+            continue;
+        }
+        let mut destination = None;
+        let mut mac = None;
+        for attribute in message.attributes {
+            match attribute {
+                NeighbourAttribute::Destination(NeighbourAddress::Inet6(a)) => {
+                    destination = Some(a)
+                }
+                NeighbourAttribute::LinkLocalAddress(bytes) => {
+                    mac = <[u8; 6]>::try_from(bytes).ok().map(MacAddr::from_octets)
+                }
+                _ => {}
+            }
+        }
+        if destination == Some(address) {
+            if let Some(mac) = mac.filter(|mac| *mac != MacAddr::zero()) {
+                return Ok(Some(mac));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(target_os = "linux")]
+async fn neighbour(address: IpAddr, ifindex: Option<u32>) -> Result<Option<MacAddr>> {
+    let mac = match address {
+        IpAddr::V4(address) => ipv4_neighbour(address, ifindex)?,
+        IpAddr::V6(address) => ipv6_neighbour(address, ifindex).await?,
+    };
+    debug!("Neighbour {}: {:?}", address, mac);
+    Ok(mac)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn neighbour(address: IpAddr, _ifindex: Option<u32>) -> Result<Option<MacAddr>> {
+    debug!("Neighbour lookup for {} is not supported", address);
+    Err(crate::error::Error::Unsupported(
+        "neighbour table lookup".to_string(),
+    ))
+}
+
+/// Look up the link-layer address of `address` in the kernel neighbour table.
+///
+/// `ifindex` restricts the lookup to one interface, which matters for link-local gateways.
+/// Returns `None` if there is no complete entry for the address.
+#[maybe_async::async_impl]
+pub async fn get_neighbour_mac(address: IpAddr, ifindex: Option<u32>) -> Result<Option<MacAddr>> {
+    neighbour(address, ifindex).await
+}
+
+/// Look up the link-layer address of `address` in the kernel neighbour table.
+///
+/// `ifindex` restricts the lookup to one interface, which matters for link-local gateways.
+/// Returns `None` if there is no complete entry for the address.
+#[maybe_async::sync_impl]
+pub fn get_neighbour_mac(address: IpAddr, ifindex: Option<u32>) -> Result<Option<MacAddr>> {
+    crate::block_on(neighbour(address, ifindex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_arp() {
+        let table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.0.2.1        0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0
+192.0.2.2        0x1         0x0         00:00:00:00:00:00     *        eth0
+192.0.2.3        0x1         0x6         11:22:33:44:55:66     *        wlan0
+";
+        assert_eq!(
+            parse_arp_table(table),
+            vec![
+                (
+                    Ipv4Addr::new(192, 0, 2, 1),
+                    MacAddr::new(0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff),
+                    "eth0".to_string()
+                ),
+                (
+                    Ipv4Addr::new(192, 0, 2, 3),
+                    MacAddr::new(0x11, 0x22, 0x33, 0x44, 0x55, 0x66),
+                    "wlan0".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    error::{Error, Result},
+    network_state::NetworkState,
+    statistics::Statistics,
+    ObserverConfig,
+};
+use std::path::Path;
+
+/// Move the calling thread into the network namespace at `path`, other threads are not affected.
+#[cfg(target_os = "linux")]
+pub fn enter(path: &Path) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let error = |e: std::io::Error| Error::NamespaceError(format!("{}: {}", path.display(), e));
+    let file = std::fs::File::open(path).map_err(error)?;
+    // SAFETY: the descriptor is open for the duration of the call
+    if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(error(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Move the calling thread into the network namespace at `path`, not supported on this platform.
+#[cfg(not(target_os = "linux"))]
+pub fn enter(_path: &Path) -> Result<()> {
+    Err(Error::Unsupported("network namespaces".to_string()))
+}
+
+/// Whether `path` is a network namespace file, such as a bind mount made by `ip netns add`.
+#[cfg(target_os = "linux")]
+pub fn is_network_namespace(path: &Path) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    // SAFETY: the descriptor is open for the duration of the call, fails on other files
+    unsafe { libc::ioctl(file.as_raw_fd(), libc::NS_GET_NSTYPE) == libc::CLONE_NEWNET }
+}
+
+/// Whether `path` is a network namespace file, never on this platform.
+#[cfg(not(target_os = "linux"))]
+pub fn is_network_namespace(_path: &Path) -> bool {
+    false
+}
+
+/// Probe the state on a thread inside the network namespace at `path`.
+#[maybe_async::async_impl]
+pub(crate) async fn probe_state_in(
+    path: &Path,
+    config: &ObserverConfig,
+    previous: Option<&Statistics>,
+) -> Result<NetworkState> {
+    let (path, config, previous) = (path.to_path_buf(), config.clone(), previous.cloned());
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let state = enter(&path).and_then(|_| {
+            // netlink sockets stay in the namespace they were opened in
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            Ok(runtime.block_on(crate::probe_state(&config, previous.as_ref())))
+        });
+        let _ = sender.send(state);
+    });
+    receiver
+        .await
+        .map_err(|_| Error::NamespaceError("probe thread panicked".to_string()))?
+}
+
+/// Probe the state on a thread inside the network namespace at `path`.
+#[maybe_async::sync_impl]
+pub(crate) fn probe_state_in(
+    path: &Path,
+    config: &ObserverConfig,
+    previous: Option<&Statistics>,
+) -> Result<NetworkState> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                enter(path)?;
+                Ok(crate::probe_state(config, previous))
+            })
+            .join()
+            .map_err(|_| Error::NamespaceError("probe thread panicked".to_string()))?
+    })
+}
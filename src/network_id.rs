@@ -0,0 +1,203 @@
+use crate::{
+    error::{Error, Result},
+    network_state::{seal, unseal, MacAddr, NetworkState},
+    ObserverConfig,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fmt, net::IpAddr};
+
+/// Fingerprint of the physical network the host is attached to.
+///
+/// Derived from the default interface, its IPv4 subnet and the default gateway's address and
+/// link-layer address, so networks sharing a gateway IP are told apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetworkId(String);
+
+impl NetworkId {
+    /// Compute the id of the network in `state`, `None` without a default interface.
+    pub fn from_state(state: &NetworkState) -> Option<Self> {
+        let interface = state.default_interface.observed()?;
+        let gateway = interface.gateway.as_ref();
+        let gateway_ip: Option<IpAddr> = state
+            .default_route
+            .observed()
+            .and_then(|route| route.hops()[0].0)
+            .or_else(|| gateway.and_then(|g| g.ipv4.first()).map(|ip| (*ip).into()));
+        let gateway_mac: Option<MacAddr> = state
+            .gateway_mac
+            .observed()
+            .copied()
+            .or_else(|| gateway.map(|g| g.mac_addr));
+        let subnet = interface
+            .ipv4
+            .first()
+            .map(|net| format!("{}/{}", net.network(), net.prefix_len));
+
+        let mut hasher = Sha256::new();
+        for component in [
+            Some(interface.name.clone()),
+            subnet,
+            gateway_ip.map(|ip| ip.to_string()),
+            gateway_mac.map(|mac| mac.to_string()),
+        ] {
+            hasher.update(component.unwrap_or_default());
+            hasher.update([0]);
+        }
+        let digest = hasher.finalize();
+        Some(Self(
+            digest[..8].iter().map(|b| format!("{:02x}", b)).collect(),
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for NetworkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Registry of user labelled networks.
+#[derive(Serialize, Deserialize, Default, PartialEq, Debug, Clone)]
+pub struct KnownNetworks(HashMap<NetworkId, String>);
+
+impl KnownNetworks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Label a network, replacing any previous label.
+    pub fn insert(&mut self, id: NetworkId, label: impl Into<String>) {
+        self.0.insert(id, label.into());
+    }
+
+    pub fn remove(&mut self, id: &NetworkId) -> Option<String> {
+        self.0.remove(id)
+    }
+
+    pub fn label(&self, id: &NetworkId) -> Option<&str> {
+        self.0.get(id).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NetworkId, &str)> {
+        self.0.iter().map(|(id, label)| (id, label.as_str()))
+    }
+
+    /// Save the networks to the configured store, with the same encryption and integrity
+    /// trailer as the state. Nothing is saved without a store for known networks.
+    pub fn save_with(&self, config: &ObserverConfig) -> Result<()> {
+        match config.known_networks_store() {
+            Some(store) => store.save(&seal(serde_json::to_vec(&self)?, config)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Load the networks from the configured store, re-encrypting them with the current key
+    /// when they were decrypted with a previous one.
+    pub fn load_with(config: &ObserverConfig) -> Result<Self> {
+        let store = config.known_networks_store().ok_or(Error::StateNotFound)?;
+        let (data, rotated) = unseal(store.load()?, config)?;
+        let known: Self = serde_json::from_slice(&data)?;
+        if rotated {
+            if let Err(e) = known.save_with(config) {
+                warn!(
+                    "Failed to re-encrypt known networks with the current key: {}",
+                    e
+                );
+            }
+        }
+        Ok(known)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrity::Integrity,
+        network_state::{Observation, Route},
+        state_store::{MemoryStore, StateStore},
+    };
+    use netdev::Interface;
+
+    fn state(gateway_mac: MacAddr) -> NetworkState {
+        let mut interface = Interface::dummy();
+        interface.name = "eth0".to_string();
+        let mut state = NetworkState::new();
+        state.default_interface = Observation::Observed(interface);
+        state.default_route = Observation::Observed(Route::new(
+            "0.0.0.0".parse().unwrap(),
+            0,
+            Some("192.168.1.1".parse().unwrap()),
+            None,
+        ));
+        state.gateway_mac = Observation::Observed(gateway_mac);
+        state
+    }
+
+    #[test]
+    fn network_id() {
+        let home = state(MacAddr::new(0, 0, 0, 0, 0, 1));
+        let cafe = state(MacAddr::new(0, 0, 0, 0, 0, 2));
+        assert_eq!(NetworkId::from_state(&home), NetworkId::from_state(&home));
+        assert_ne!(NetworkId::from_state(&home), NetworkId::from_state(&cafe));
+
+        let mut offline = NetworkState::new();
+        offline.default_interface = Observation::Absent;
+        assert_eq!(NetworkId::from_state(&offline), None);
+    }
+
+    #[test]
+    fn known_networks() {
+        let store = MemoryStore::new();
+        let config = ObserverConfig::default()
+            .set_store(MemoryStore::new())
+            .set_known_networks_store(store.clone())
+            .set_integrity(Integrity::Checksum);
+        let id = NetworkId::from_state(&state(MacAddr::new(0, 0, 0, 0, 0, 1))).unwrap();
+        let mut known = KnownNetworks::new();
+        known.insert(id.clone(), "home");
+        known.save_with(&config).unwrap();
+
+        let loaded = KnownNetworks::load_with(&config).unwrap();
+        assert_eq!(loaded.label(&id), Some("home"));
+
+        // sealed like the state
+        let mut data = store.load().unwrap();
+        data[0] ^= 1;
+        store.save(&data).unwrap();
+        assert!(matches!(
+            KnownNetworks::load_with(&config),
+            Err(Error::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn known_networks_are_encrypted() {
+        use crate::encryption::EncryptionKey;
+
+        let store = MemoryStore::new();
+        let config = ObserverConfig::default()
+            .set_known_networks_store(store.clone())
+            .set_encryption_key(EncryptionKey::Bytes(b"secret".to_vec()));
+        let id = NetworkId::from_state(&state(MacAddr::new(0, 0, 0, 0, 0, 1))).unwrap();
+        let mut known = KnownNetworks::new();
+        known.insert(id.clone(), "home");
+        known.save_with(&config).unwrap();
+
+        assert!(serde_json::from_slice::<KnownNetworks>(&store.load().unwrap()).is_err());
+        assert!(KnownNetworks::load_with(
+            &config
+                .clone()
+                .set_encryption_key(EncryptionKey::Bytes(b"other".to_vec()))
+        )
+        .is_err());
+        assert_eq!(KnownNetworks::load_with(&config).unwrap(), known);
+    }
+}
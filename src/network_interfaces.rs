@@ -1,9 +1,71 @@
+use crate::{link::LinkProperties, statistics::Statistics, NetworkChange};
 use netdev::Interface;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
-pub struct Interfaces(HashMap<String, Interface>);
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(from = "Format")]
+pub struct Interfaces {
+    interfaces: HashMap<String, Interface>,
+    /// Link properties by interface name.
+    #[serde(default)]
+    links: HashMap<String, LinkProperties>,
+    #[serde(default)]
+    statistics: Option<Statistics>,
+}
+
+/// The current format, or the map of interfaces by name persisted before link properties.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Format {
+    Current {
+        interfaces: HashMap<String, Interface>,
+        #[serde(default)]
+        links: HashMap<String, LinkProperties>,
+        #[serde(default)]
+        statistics: Option<Statistics>,
+    },
+    Legacy(HashMap<String, Interface>),
+}
+
+impl From<Format> for Interfaces {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Current {
+                interfaces,
+                links,
+                statistics,
+            } => Self {
+                interfaces,
+                links,
+                statistics,
+            },
+            Format::Legacy(interfaces) => Self {
+                interfaces,
+                links: HashMap::new(),
+                statistics: None,
+            },
+        }
+    }
+}
+
+/// Clear the fields netdev reads from `/proc/net`, `/sys/class/net` and `/etc/resolv.conf`,
+/// which show the namespace of the process rather than that of the calling thread: the
+/// gateway, type, speeds and DNS servers.
+pub fn clear_host_fields(interface: &mut Interface) {
+    interface.gateway = None;
+    interface.if_type = netdev::interface::InterfaceType::Unknown;
+    interface.transmit_speed = None;
+    interface.receive_speed = None;
+    interface.dns_servers.clear();
+}
+
+// traffic counters change all the time and are compared against thresholds instead
+impl PartialEq for Interfaces {
+    fn eq(&self, other: &Self) -> bool {
+        self.interfaces == other.interfaces && self.links == other.links
+    }
+}
 
 impl Interfaces {
     pub fn new(interfaces: Vec<Interface>) -> Self {
@@ -11,12 +73,114 @@ impl Interfaces {
         for interface in interfaces {
             set.insert(interface.name.to_owned(), interface);
         }
-        Self(set)
+        Self {
+            interfaces: set,
+            links: HashMap::new(),
+            statistics: None,
+        }
+    }
+
+    pub fn with_links(mut self, links: HashMap<String, LinkProperties>) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn with_statistics(mut self, statistics: Statistics) -> Self {
+        self.statistics = Some(statistics);
+        self
+    }
+
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Compare interfaces by name with `eq`, and link properties with `PartialEq`.
+    pub fn eq_by(&self, other: &Self, eq: impl Fn(&Interface, &Interface) -> bool) -> bool {
+        self.interfaces.len() == other.interfaces.len()
+            && self.interfaces.iter().all(|(name, interface)| {
+                other
+                    .interfaces
+                    .get(name)
+                    .is_some_and(|other| eq(interface, other))
+            })
+            && self.links == other.links
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Interface> {
+        self.interfaces.get(name)
+    }
+
+    pub fn link(&self, name: &str) -> Option<&LinkProperties> {
+        self.links.get(name)
+    }
+
+    /// Interfaces whose link kind is `kind`.
+    pub fn of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a Interface> {
+        self.interfaces.values().filter(move |interface| {
+            self.link(&interface.name)
+                .is_some_and(|link| link.kind.as_deref() == Some(kind))
+        })
+    }
+
+    /// Describe the named interface by its link kind and master, such as
+    /// `veth for bridge docker0`.
+    pub fn describe(&self, name: &str) -> String {
+        let link = self.link(name);
+        let kind = link
+            .and_then(|link| link.kind.as_deref())
+            .unwrap_or("interface");
+        match link.and_then(|link| link.master.as_deref()) {
+            Some(master) => {
+                let master_kind = self
+                    .link(master)
+                    .and_then(|link| link.kind.as_deref())
+                    .unwrap_or("master");
+                format!("{} for {} {}", kind, master_kind, master)
+            }
+            None => kind.to_string(),
+        }
+    }
+
+    /// Report an interface removed or added, otherwise the first link property change of an
+    /// interface present in both snapshots. Only interfaces with link properties are compared.
+    pub fn compare_links(&self, other: &Self) -> NetworkChange {
+        let mut names: Vec<&String> = self.links.keys().collect();
+        names.sort();
+        if let Some(name) = names.iter().find(|name| !other.links.contains_key(**name)) {
+            return NetworkChange::InterfaceRemoved((*name).clone());
+        }
+        let mut added: Vec<&String> = other
+            .links
+            .keys()
+            .filter(|name| !self.links.contains_key(*name))
+            .collect();
+        added.sort();
+        if let Some(name) = added.first() {
+            return NetworkChange::InterfaceAdded((*name).clone());
+        }
+        for name in names {
+            let (Some(old), Some(new)) = (self.link(name), other.link(name)) else {
+                continue;
+            };
+            if old.carrier != new.carrier {
+                return NetworkChange::Carrier(name.clone());
+            }
+            if old.operstate != new.operstate {
+                return NetworkChange::OperState(name.clone());
+            }
+            if old.speed != new.speed {
+                return NetworkChange::LinkSpeed(name.clone());
+            }
+            if old.duplex != new.duplex {
+                return NetworkChange::Duplex(name.clone());
+            }
+        }
+        NetworkChange::None
     }
 
     pub fn diff(&self, other: &Self) -> InterfacesDiff {
-        let lhs = self.0.to_owned();
-        let rhs = other.0.to_owned();
+        let lhs = self.interfaces.to_owned();
+        let rhs = other.interfaces.to_owned();
         let mut updated = HashMap::new();
         let mut added = HashMap::new();
         let mut removed = HashMap::new();
@@ -69,6 +233,45 @@ mod should {
         interface
     }
 
+    #[test]
+    fn clear_host_fields_of_namespaced_interfaces() {
+        let mut host = interface("eth0");
+        host.index = 2;
+        host.if_type = netdev::interface::InterfaceType::Ethernet;
+        host.ipv4 = vec![netdev::ip::Ipv4Net::new("10.0.0.2".parse().unwrap(), 24)];
+        host.transmit_speed = Some(1_000_000_000);
+        host.receive_speed = Some(1_000_000_000);
+        host.gateway = Some(netdev::NetworkDevice::new());
+        host.dns_servers = vec!["10.0.0.1".parse().unwrap()];
+        host.default = true;
+
+        let mut cleared = host.clone();
+        clear_host_fields(&mut cleared);
+        let mut expected = host;
+        expected.if_type = netdev::interface::InterfaceType::Unknown;
+        expected.transmit_speed = None;
+        expected.receive_speed = None;
+        expected.gateway = None;
+        expected.dns_servers = vec![];
+        // the index, addresses and default flag are read in the namespace and kept
+        assert_eq!(cleared, expected);
+    }
+
+    #[test]
+    fn deserialize_legacy_map() {
+        let interfaces = Interfaces::new(vec![interface("eth0"), interface("eth1")]);
+        let legacy = serde_json::to_string(&interfaces.interfaces).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Interfaces>(&legacy).unwrap(),
+            interfaces
+        );
+        let current = serde_json::to_string(&interfaces).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Interfaces>(&current).unwrap(),
+            interfaces
+        );
+    }
+
     #[test]
     fn diff_same() {
         let old = Interfaces::new(vec![interface("eth0")]);
@@ -146,4 +349,87 @@ mod should {
         };
         assert_eq!(diff, expected_diff);
     }
+
+    #[test]
+    fn compare_links() {
+        let link = |carrier, speed| LinkProperties {
+            carrier: Some(carrier),
+            speed: Some(speed),
+            ..Default::default()
+        };
+        let links = |link: LinkProperties| vec![("eth0".to_string(), link)].into_iter().collect();
+        let old = Interfaces::new(vec![interface("eth0")]).with_links(links(link(true, 1000)));
+        let renegotiated =
+            Interfaces::new(vec![interface("eth0")]).with_links(links(link(true, 100)));
+        let unplugged =
+            Interfaces::new(vec![interface("eth0")]).with_links(links(link(false, 100)));
+
+        assert_eq!(old.compare_links(&old), NetworkChange::None);
+        assert_eq!(
+            old.compare_links(&renegotiated),
+            NetworkChange::LinkSpeed("eth0".to_string())
+        );
+        assert_eq!(
+            old.compare_links(&unplugged),
+            NetworkChange::Carrier("eth0".to_string())
+        );
+        assert_ne!(old, renegotiated);
+    }
+
+    #[test]
+    fn containers() {
+        let link = |kind: Option<&str>, master: Option<&str>| LinkProperties {
+            kind: kind.map(str::to_string),
+            master: master.map(str::to_string),
+            ..Default::default()
+        };
+        let old = Interfaces::new(vec![interface("eth0"), interface("docker0")]).with_links(
+            vec![
+                ("eth0".to_string(), link(None, None)),
+                ("docker0".to_string(), link(Some("bridge"), None)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut links = old.links.clone();
+        links.insert(
+            "veth1a2b3c".to_string(),
+            link(Some("veth"), Some("docker0")),
+        );
+        let new = Interfaces::new(vec![
+            interface("eth0"),
+            interface("docker0"),
+            interface("veth1a2b3c"),
+        ])
+        .with_links(links);
+
+        let change = old.compare_links(&new);
+        assert_eq!(
+            change,
+            NetworkChange::InterfaceAdded("veth1a2b3c".to_string())
+        );
+        assert_eq!(new.describe("veth1a2b3c"), "veth for bridge docker0");
+        assert_eq!(new.describe("eth0"), "interface");
+        assert_eq!(
+            new.compare_links(&old),
+            NetworkChange::InterfaceRemoved("veth1a2b3c".to_string())
+        );
+        assert_eq!(new.of_kind("veth").count(), 1);
+    }
+
+    #[test]
+    fn ignore_statistics() {
+        use crate::statistics::InterfaceCounters;
+
+        let counters = |rx_bytes| {
+            let counters = InterfaceCounters {
+                rx_bytes,
+                ..Default::default()
+            };
+            Statistics::new(vec![("eth0".to_string(), counters)].into_iter().collect())
+        };
+        let old = Interfaces::new(vec![interface("eth0")]).with_statistics(counters(1000));
+        let new = Interfaces::new(vec![interface("eth0")]).with_statistics(counters(2000));
+        assert_eq!(old, new);
+    }
 }
@@ -1,31 +1,51 @@
-use super::{NetworkChange, ObserverConfig};
+use super::{NetworkChange, ObserverConfig, Probe};
+pub use crate::connectivity::Connectivity;
+pub use crate::dns::DnsConfig;
 use crate::error::Result;
+pub use crate::neighbours::MacAddr;
+pub use crate::network_id::NetworkId;
 pub use crate::network_interfaces::Interfaces;
-pub use crate::routes::Route;
-use directories::ProjectDirs;
-use log::trace;
+pub use crate::observation::Observation;
+pub use crate::routes::{DefaultRoutes, Route, RoutingTable};
+pub use crate::rules::RoutingRules;
+pub use crate::state_store::get_data_path;
+pub use crate::vpn::Vpns;
+pub use crate::wireless::WirelessInterfaces;
+use log::warn;
 use netdev::Interface;
 use serde::{Deserialize, Serialize};
-use std::{
-    fs::{self, File},
-    io::{Read, Write},
-    net::IpAddr,
-    time::SystemTime,
-};
+use std::{net::IpAddr, time::SystemTime};
 
 #[cfg(feature = "encryption")]
-use crate::error::Error;
-#[cfg(feature = "encryption")]
-use cocoon::Cocoon;
+use crate::encryption::{decrypt_with_any, encrypt};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct NetworkState {
     pub last_update: SystemTime,
-    pub default_interface: Option<Interface>,
-    pub all_interfaces: Option<Interfaces>,
-    pub default_route: Option<Route>,
-    pub all_routes: Option<Vec<Route>>,
-    pub public_address: Option<IpAddr>,
+    pub default_interface: Observation<Interface>,
+    pub all_interfaces: Observation<Interfaces>,
+    pub default_route: Observation<Route>,
+    /// Default routes of each address family.
+    #[serde(default)]
+    pub default_routes: Observation<DefaultRoutes>,
+    pub all_routes: Observation<RoutingTable>,
+    /// Policy routing rules of both address families.
+    #[serde(default)]
+    pub routing_rules: Observation<RoutingRules>,
+    pub public_address: Observation<IpAddr>,
+    #[serde(default)]
+    pub dns_config: Observation<DnsConfig>,
+    #[serde(default)]
+    pub connectivity: Observation<Connectivity>,
+    /// Link-layer address of the default gateway.
+    #[serde(default)]
+    pub gateway_mac: Observation<MacAddr>,
+    #[serde(default)]
+    pub network_id: Observation<NetworkId>,
+    #[serde(default)]
+    pub wireless: Observation<WirelessInterfaces>,
+    #[serde(default)]
+    pub vpns: Observation<Vpns>,
 }
 
 impl Default for NetworkState {
@@ -38,12 +58,104 @@ impl NetworkState {
     pub fn new() -> Self {
         Self {
             last_update: SystemTime::now(),
-            default_interface: netdev::get_default_interface().ok(),
-            all_interfaces: None,
-            default_route: None,
-            all_routes: None,
-            public_address: None,
+            // netdev fails when there is no default interface
+            default_interface: Observation::from_option(netdev::get_default_interface().ok()),
+            all_interfaces: Observation::NotObserved,
+            default_route: Observation::NotObserved,
+            default_routes: Observation::NotObserved,
+            all_routes: Observation::NotObserved,
+            routing_rules: Observation::NotObserved,
+            public_address: Observation::NotObserved,
+            dns_config: Observation::NotObserved,
+            connectivity: Observation::NotObserved,
+            gateway_mac: Observation::NotObserved,
+            network_id: Observation::NotObserved,
+            wireless: Observation::NotObserved,
+            vpns: Observation::NotObserved,
+        }
+    }
+
+    /// A state whose components observed by `config` are all unknown for `reason`.
+    pub fn unknown(config: &ObserverConfig, reason: &str) -> Self {
+        fn unknown<T>(observed: bool, reason: &str) -> Observation<T> {
+            match observed {
+                true => Observation::Unknown(reason.to_string()),
+                false => Observation::NotObserved,
+            }
+        }
+        Self {
+            last_update: SystemTime::now(),
+            default_interface: Observation::Unknown(reason.to_string()),
+            all_interfaces: unknown(
+                config.observe_all_interfaces || config.statistics_thresholds.is_some(),
+                reason,
+            ),
+            default_route: unknown(config.observe_default_route, reason),
+            default_routes: unknown(config.observe_default_route, reason),
+            all_routes: unknown(
+                config.observe_all_routes || !config.watched_destinations.is_empty(),
+                reason,
+            ),
+            routing_rules: unknown(config.observe_routing_rules, reason),
+            public_address: unknown(config.observe_public_address, reason),
+            dns_config: unknown(config.observe_dns_config, reason),
+            connectivity: unknown(config.connectivity_check.is_some(), reason),
+            gateway_mac: unknown(config.observe_gateway_mac, reason),
+            network_id: unknown(config.observe_network_id, reason),
+            wireless: unknown(config.observe_wireless, reason),
+            vpns: unknown(config.observe_vpn, reason),
+        }
+    }
+
+    /// Probes whose components are unknown because the probe failed.
+    pub fn failed_probes(&self) -> Vec<Probe> {
+        let mut probes = Vec::new();
+        if self.default_route.is_unknown() {
+            probes.push(Probe::DefaultRoute);
+        }
+        if self.all_routes.is_unknown() {
+            probes.push(Probe::AllRoutes);
         }
+        if self.routing_rules.is_unknown() {
+            probes.push(Probe::RoutingRules);
+        }
+        if self.public_address.is_unknown() {
+            probes.push(Probe::PublicAddress);
+        }
+        if self.dns_config.is_unknown() {
+            probes.push(Probe::DnsConfig);
+        }
+        if self.connectivity.is_unknown() {
+            probes.push(Probe::Connectivity);
+        }
+        if self.gateway_mac.is_unknown() {
+            probes.push(Probe::GatewayMac);
+        }
+        if self.wireless.is_unknown() {
+            probes.push(Probe::Wireless);
+        }
+        if self.vpns.is_unknown() {
+            probes.push(Probe::Vpn);
+        }
+        probes
+    }
+
+    /// Update the state with `new`, keeping known components that are unknown in `new`.
+    pub fn update(&mut self, new: Self) {
+        self.last_update = new.last_update;
+        self.default_interface.update(new.default_interface);
+        self.all_interfaces.update(new.all_interfaces);
+        self.default_route.update(new.default_route);
+        self.default_routes.update(new.default_routes);
+        self.all_routes.update(new.all_routes);
+        self.routing_rules.update(new.routing_rules);
+        self.public_address.update(new.public_address);
+        self.dns_config.update(new.dns_config);
+        self.connectivity.update(new.connectivity);
+        self.gateway_mac.update(new.gateway_mac);
+        self.network_id.update(new.network_id);
+        self.wireless.update(new.wireless);
+        self.vpns.update(new.vpns);
     }
 
     pub fn compare(&self, other: &Self, config: &ObserverConfig) -> NetworkChange {
@@ -58,22 +170,131 @@ impl NetworkState {
             return NetworkChange::Expired;
         }
 
-        // check default interface
-        if self.default_interface != other.default_interface {
+        // unknown components are skipped
+        if config.observe_network_id && self.network_id.differs(&other.network_id) {
+            // labels of known networks are resolved by the observer
+            if let Observation::Observed(id) = &other.network_id {
+                return NetworkChange::JoinedUnknownNetwork(id.clone());
+            }
+        }
+        // a full tunnel also changes the default interface and route
+        if config.observe_vpn {
+            if let (Observation::Observed(old), Observation::Observed(new)) =
+                (&self.vpns, &other.vpns)
+            {
+                let change = old.compare(new);
+                if change != NetworkChange::None {
+                    return change;
+                }
+            }
+        }
+        let comparator = config.comparator();
+        if self
+            .default_interface
+            .differs_by(&other.default_interface, |a, b| {
+                comparator.interface_eq(a, b)
+            })
+        {
             return NetworkChange::DefaultInterface;
         }
-        if config.observe_all_interfaces && self.all_interfaces != other.all_interfaces {
+        if config.observe_all_interfaces {
+            if let (Observation::Observed(old), Observation::Observed(new)) =
+                (&self.all_interfaces, &other.all_interfaces)
+            {
+                let change = old.compare_links(new);
+                if change != NetworkChange::None {
+                    return change;
+                }
+            }
+        }
+        if let Some(thresholds) = &config.statistics_thresholds {
+            if let (Some(old), Some(new)) = (
+                self.all_interfaces
+                    .observed()
+                    .and_then(Interfaces::statistics),
+                other
+                    .all_interfaces
+                    .observed()
+                    .and_then(Interfaces::statistics),
+            ) {
+                let change = old.compare(new, thresholds);
+                if change != NetworkChange::None {
+                    return change;
+                }
+            }
+        }
+        if config.observe_all_interfaces
+            && self
+                .all_interfaces
+                .differs_by(&other.all_interfaces, |a, b| comparator.interfaces_eq(a, b))
+        {
             return NetworkChange::SecondaryInterface;
         }
-        if config.observe_default_route && self.default_route != other.default_route {
+        // a change of the preferred route takes precedence over the per-family changes
+        if config.observe_default_route
+            && self
+                .default_route
+                .differs_by(&other.default_route, |a, b| comparator.route_eq(a, b))
+        {
             return NetworkChange::DefaultRoute;
         }
-        if config.observe_all_routes && self.all_routes != other.all_routes {
+        if config.observe_default_route {
+            if let (Observation::Observed(old), Observation::Observed(new)) =
+                (&self.default_routes, &other.default_routes)
+            {
+                if !comparator.routes_eq(&old.ipv4, &new.ipv4) {
+                    return NetworkChange::DefaultRouteV4;
+                }
+                if !comparator.routes_eq(&old.ipv6, &new.ipv6) {
+                    return NetworkChange::DefaultRouteV6;
+                }
+            }
+        }
+        if let (Observation::Observed(old), Observation::Observed(new)) =
+            (&self.all_routes, &other.all_routes)
+        {
+            for destination in &config.watched_destinations {
+                let changed = match (old.lookup(*destination), new.lookup(*destination)) {
+                    (Some(a), Some(b)) => !comparator.route_eq(a, b),
+                    (a, b) => a.is_some() != b.is_some(),
+                };
+                if changed {
+                    return NetworkChange::WatchedRoute(*destination);
+                }
+            }
+        }
+        if config.observe_all_routes
+            && self
+                .all_routes
+                .differs_by(&other.all_routes, |a, b| comparator.routes_eq(a, b))
+        {
             return NetworkChange::RoutingTable;
         }
-        if config.observe_public_address && self.public_address != other.public_address {
+        if config.observe_routing_rules && self.routing_rules.differs(&other.routing_rules) {
+            return NetworkChange::RoutingRules;
+        }
+        if config.observe_public_address && self.public_address.differs(&other.public_address) {
             return NetworkChange::PublicAddress;
         }
+        if config.observe_dns_config && self.dns_config.differs(&other.dns_config) {
+            return NetworkChange::DnsConfig;
+        }
+        if config.connectivity_check.is_some() && self.connectivity.differs(&other.connectivity) {
+            return NetworkChange::Connectivity;
+        }
+        if config.observe_gateway_mac && self.gateway_mac.differs(&other.gateway_mac) {
+            return NetworkChange::GatewayMac;
+        }
+        if config.observe_wireless {
+            if let (Observation::Observed(old), Observation::Observed(new)) =
+                (&self.wireless, &other.wireless)
+            {
+                let change = old.compare(new);
+                if change != NetworkChange::None {
+                    return change;
+                }
+            }
+        }
 
         NetworkChange::None
     }
@@ -89,83 +310,67 @@ impl NetworkState {
         Ok(deserialized)
     }
 
+    /// Save the state to the default file store.
     pub fn save(&self) -> Result<()> {
-        let data = self.encode()?;
-
-        #[cfg(feature = "encryption")]
-        let data = encrypt(data)?;
-
-        let mut file = File::create(get_data_path())?;
-        file.write_all(&data)?;
-        Ok(())
+        self.save_with(&ObserverConfig::default())
     }
 
+    /// Load the state from the default file store.
     pub fn load() -> Result<Self> {
-        trace!("Loading state from {}", get_data_path());
-        let mut file = File::open(get_data_path())?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
-
-        #[cfg(feature = "encryption")]
-        let data = decrypt(data)?;
+        Self::load_with(&ObserverConfig::default())
+    }
 
-        Self::decode(data)
+    /// Save the state to the configured store, with the configured encryption and integrity trailer.
+    pub fn save_with(&self, config: &ObserverConfig) -> Result<()> {
+        config.store().save(&seal(self.encode()?, config)?)
     }
-}
 
-#[cfg(feature = "encryption")]
-fn decrypt(data: Vec<u8>) -> Result<Vec<u8>> {
-    trace!("Decrypting data");
-    let password = mid::get(env!("CARGO_PKG_NAME")).unwrap_or("network_state".to_string());
-    let cocoon = if cfg!(debug_assertions) {
-        Cocoon::new(password.as_bytes()).with_weak_kdf()
-    } else {
-        Cocoon::new(password.as_bytes())
-    };
-    match cocoon.unwrap(&data) {
-        Ok(data) => Ok(data),
-        Err(e) => Err(Error::EncryptionError(format!("Error decrypting: {:?}", e))),
+    /// Load the state from the configured store, verifying the configured integrity trailer.
+    ///
+    /// With encryption, state decrypted with a previous key is re-encrypted with the current key.
+    pub fn load_with(config: &ObserverConfig) -> Result<Self> {
+        let (data, rotated) = unseal(config.store().load()?, config)?;
+        let state = Self::decode(data)?;
+        if rotated {
+            if let Err(e) = state.save_with(config) {
+                warn!("Failed to re-encrypt state with the current key: {}", e);
+            }
+        }
+        Ok(state)
     }
 }
 
-#[cfg(feature = "encryption")]
-fn encrypt(data: Vec<u8>) -> Result<Vec<u8>> {
-    trace!("Encrypting data");
-    let password = mid::get(env!("CARGO_PKG_NAME")).unwrap_or("network_state".to_string());
-    let mut cocoon = if cfg!(debug_assertions) {
-        Cocoon::new(password.as_bytes()).with_weak_kdf()
-    } else {
-        Cocoon::new(password.as_bytes())
-    };
-    match cocoon.wrap(&data) {
-        Ok(data) => Ok(data),
-        Err(e) => Err(Error::EncryptionError(format!("Error encrypting: {:?}", e))),
-    }
+/// Encrypt `data` with the configured key and seal it with the configured integrity trailer.
+pub(crate) fn seal(data: Vec<u8>, config: &ObserverConfig) -> Result<Vec<u8>> {
+    #[cfg(feature = "encryption")]
+    let data = encrypt(data, &config.encryption_key)?;
+
+    config.integrity.seal(data)
 }
 
-pub fn get_data_path() -> String {
-    let file_name = "state.cache";
-    if let Some(base_dirs) = ProjectDirs::from("", "", env!("CARGO_PKG_NAME")) {
-        let mut dir = base_dirs.data_dir();
-        // Create directory if it doesn't exist
-        if !dir.exists() && fs::create_dir_all(dir).is_err() {
-            // If we can't create the data directory, fallback to cache directory
-            dir = base_dirs.cache_dir();
-            if !dir.exists() && fs::create_dir_all(dir).is_err() {
-                dir = base_dirs.config_dir();
-            }
-        }
-        if let Some(path) = dir.join(file_name).to_str() {
-            return path.to_string();
-        }
-    };
-    // If we can't get any directory, fallback to current directory
-    file_name.to_string()
+/// Verify the configured integrity trailer of `data` and decrypt it, also reporting whether
+/// a previous key was needed.
+pub(crate) fn unseal(data: Vec<u8>, config: &ObserverConfig) -> Result<(Vec<u8>, bool)> {
+    let data = config.integrity.verify(data)?;
+
+    #[cfg(feature = "encryption")]
+    return decrypt_with_any(
+        data,
+        &config.encryption_key,
+        &config.previous_encryption_keys,
+    );
+    #[cfg(not(feature = "encryption"))]
+    Ok((data, false))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::integrity::Integrity;
+    use crate::state_store::{MemoryStore, StateStore};
+
+    #[cfg(feature = "encryption")]
+    use crate::encryption::EncryptionKey;
 
     #[test]
     fn test_encode_decode() {
@@ -175,21 +380,181 @@ mod tests {
         assert_eq!(state, decoded);
     }
 
+    #[test]
+    fn test_decode_legacy() {
+        let interface = Interface::dummy();
+        let legacy = serde_json::json!({
+            "last_update": SystemTime::now(),
+            "default_interface": interface,
+            "all_interfaces": { interface.name.clone(): interface },
+            "default_route": null,
+            "all_routes": [],
+            "public_address": "192.0.2.1",
+            "failed_probes": [],
+        });
+        let state = NetworkState::decode(serde_json::to_vec(&legacy).unwrap()).unwrap();
+        assert_eq!(
+            state.default_interface,
+            Observation::Observed(interface.clone())
+        );
+        assert_eq!(
+            state.all_interfaces,
+            Observation::Observed(Interfaces::new(vec![interface]))
+        );
+        assert_eq!(state.default_route, Observation::Absent);
+        assert_eq!(
+            state.all_routes,
+            Observation::Observed(RoutingTable::default())
+        );
+        assert_eq!(
+            state.public_address,
+            Observation::Observed("192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(state.dns_config, Observation::NotObserved);
+    }
+
     #[test]
     fn test_save_load() {
-        dbg!(get_data_path());
+        let config = ObserverConfig::default().set_store(MemoryStore::new());
+        let state = NetworkState::new();
+        state.save_with(&config).unwrap();
+        let loaded = NetworkState::load_with(&config).unwrap();
+        assert_eq!(state, loaded);
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_save_load_with_key() {
+        let store = MemoryStore::new();
+        let config = ObserverConfig::default()
+            .set_store(store.clone())
+            .set_encryption_key(EncryptionKey::Bytes(b"secret".to_vec()));
         let state = NetworkState::new();
-        state.save().unwrap();
-        let loaded = NetworkState::load().unwrap();
+        state.save_with(&config).unwrap();
+        let loaded = NetworkState::load_with(&config).unwrap();
         assert_eq!(state, loaded);
+        assert!(NetworkState::load_with(&ObserverConfig::default().set_store(store)).is_err());
     }
 
     #[test]
     #[cfg(feature = "encryption")]
-    fn test_encrypt_decrypt() {
-        let data = b"hello world".to_vec();
-        let encrypted = encrypt(data.clone()).unwrap();
-        let decrypted = decrypt(encrypted).unwrap();
-        assert_eq!(data, decrypted);
+    fn test_load_with_previous_key() {
+        let old_key = EncryptionKey::Bytes(b"old".to_vec());
+        let new_key = EncryptionKey::Bytes(b"new".to_vec());
+        let config = ObserverConfig::default()
+            .set_store(MemoryStore::new())
+            .set_integrity(Integrity::Checksum)
+            .set_encryption_key(new_key);
+        let state = NetworkState::new();
+        state
+            .save_with(&config.clone().set_encryption_key(old_key.clone()))
+            .unwrap();
+
+        assert!(NetworkState::load_with(&config).is_err());
+        assert!(matches!(
+            NetworkState::load_with(
+                &config
+                    .clone()
+                    .set_integrity(Integrity::Hmac(b"secret".to_vec()))
+                    .set_previous_encryption_keys(vec![old_key.clone()])
+            ),
+            Err(crate::error::Error::IntegrityError(_))
+        ));
+        let loaded =
+            NetworkState::load_with(&config.clone().set_previous_encryption_keys(vec![old_key]))
+                .unwrap();
+        assert_eq!(state, loaded);
+        // re-encrypted with the new key and sealed again
+        let loaded = NetworkState::load_with(&config).unwrap();
+        assert_eq!(state, loaded);
+    }
+
+    #[test]
+    fn test_load_tampered() {
+        let store = MemoryStore::new();
+        let config = ObserverConfig::default()
+            .set_store(store.clone())
+            .set_integrity(Integrity::Hmac(b"secret".to_vec()));
+        let state = NetworkState::new();
+        state.save_with(&config).unwrap();
+        assert_eq!(state, NetworkState::load_with(&config).unwrap());
+
+        let mut data = store.load().unwrap();
+        data[0] ^= 1;
+        store.save(&data).unwrap();
+        assert!(matches!(
+            NetworkState::load_with(&config),
+            Err(crate::error::Error::IntegrityError(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare_skips_unknown() {
+        let config = ObserverConfig::default().enable_observe_public_address(true);
+        let mut old = NetworkState::new();
+        old.public_address = Observation::Observed(IpAddr::from([192, 0, 2, 1]));
+        let mut new = NetworkState::new();
+        new.public_address = Observation::Unknown("timeout".to_string());
+        assert_eq!(old.compare(&new, &config), NetworkChange::None);
+        assert_eq!(new.failed_probes(), vec![Probe::PublicAddress]);
+
+        new.public_address = Observation::Absent;
+        assert_eq!(old.compare(&new, &config), NetworkChange::PublicAddress);
+    }
+
+    #[test]
+    fn test_unknown_state() {
+        let config = ObserverConfig::default()
+            .enable_observe_default_route(true)
+            .enable_observe_vpn(true);
+        let old = NetworkState::new();
+        let new = NetworkState::unknown(&config, "no such namespace");
+        assert_eq!(new.failed_probes(), vec![Probe::DefaultRoute, Probe::Vpn]);
+        assert_eq!(new.public_address, Observation::NotObserved);
+        assert_eq!(old.compare(&new, &config), NetworkChange::None);
+    }
+
+    #[test]
+    fn test_compare_default_routes() {
+        let config = ObserverConfig::default().enable_observe_default_route(true);
+        let ipv4 = Route::new([0, 0, 0, 0].into(), 0, Some([192, 0, 2, 1].into()), Some(2));
+        let ipv6 = Route::new(std::net::Ipv6Addr::UNSPECIFIED.into(), 0, None, Some(2));
+        let mut old = NetworkState::new();
+        old.default_route = Observation::Observed(ipv4.clone());
+        old.default_routes = Observation::Observed(DefaultRoutes::new(vec![ipv4.clone(), ipv6]));
+        let mut new = NetworkState::new();
+        new.default_route = Observation::Observed(ipv4.clone());
+        new.default_routes = Observation::Observed(DefaultRoutes::new(vec![ipv4]));
+        assert_eq!(old.compare(&new, &config), NetworkChange::DefaultRouteV6);
+
+        let gateway = Route::new(
+            [0, 0, 0, 0].into(),
+            0,
+            Some([192, 0, 2, 254].into()),
+            Some(2),
+        );
+        new.default_route = Observation::Observed(gateway.clone());
+        new.default_routes = Observation::Observed(DefaultRoutes::new(vec![gateway]));
+        assert_eq!(old.compare(&new, &config), NetworkChange::DefaultRoute);
+    }
+
+    #[test]
+    fn test_compare_watched_destinations() {
+        let watched = IpAddr::from([10, 2, 3, 4]);
+        let config = ObserverConfig::default().set_watched_destinations([watched]);
+        let default = Route::new([0, 0, 0, 0].into(), 0, Some([192, 0, 2, 1].into()), Some(2));
+        let tunnel = Route::new([10, 0, 0, 0].into(), 8, None, Some(5));
+        let lan = Route::new([192, 168, 1, 0].into(), 24, None, Some(2));
+        let mut old = NetworkState::new();
+        old.all_routes = Observation::Observed(vec![default.clone()].into());
+        let mut new = NetworkState::new();
+        new.all_routes = Observation::Observed(vec![default.clone(), lan].into());
+        assert_eq!(old.compare(&new, &config), NetworkChange::None);
+
+        new.all_routes = Observation::Observed(vec![default, tunnel].into());
+        assert_eq!(
+            old.compare(&new, &config),
+            NetworkChange::WatchedRoute(watched)
+        );
     }
 }
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Observed value of a component of the network state.
+///
+/// Serialized with an explicit `observation` tag, so it also deserializes from the `Option`
+/// that components used to be persisted as, `null` as [`Observation::Absent`] and a value as
+/// [`Observation::Observed`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "observation", content = "value", from = "Format<T>")]
+pub enum Observation<T> {
+    /// The component is not observed per config.
+    #[default]
+    NotObserved,
+    /// The component was observed with the given value.
+    Observed(T),
+    /// The component was observed and is absent, for example there is no default route.
+    Absent,
+    /// The probe for the component failed with the given error.
+    Unknown(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "observation", content = "value", deny_unknown_fields)]
+enum Tagged<T> {
+    NotObserved,
+    Observed(T),
+    Absent,
+    Unknown(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Format<T> {
+    Tagged(Tagged<T>),
+    Legacy(Option<T>),
+}
+
+impl<T> From<Format<T>> for Observation<T> {
+    fn from(format: Format<T>) -> Self {
+        match format {
+            Format::Tagged(Tagged::NotObserved) => Self::NotObserved,
+            Format::Tagged(Tagged::Observed(value)) => Self::Observed(value),
+            Format::Tagged(Tagged::Absent) => Self::Absent,
+            Format::Tagged(Tagged::Unknown(error)) => Self::Unknown(error),
+            Format::Legacy(value) => Self::from_option(value),
+        }
+    }
+}
+
+impl<T> Observation<T> {
+    /// Observed if `Some`, absent otherwise.
+    pub fn from_option(value: Option<T>) -> Self {
+        match value {
+            Some(value) => Self::Observed(value),
+            None => Self::Absent,
+        }
+    }
+
+    /// Whether the component was observed to be present or absent.
+    pub fn is_known(&self) -> bool {
+        matches!(self, Self::Observed(_) | Self::Absent)
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+
+    /// The observed value, if any.
+    pub fn observed(&self) -> Option<&T> {
+        match self {
+            Self::Observed(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_ref(&self) -> Observation<&T> {
+        match self {
+            Self::NotObserved => Observation::NotObserved,
+            Self::Observed(value) => Observation::Observed(value),
+            Self::Absent => Observation::Absent,
+            Self::Unknown(error) => Observation::Unknown(error.clone()),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Observation<U> {
+        match self {
+            Self::NotObserved => Observation::NotObserved,
+            Self::Observed(value) => Observation::Observed(f(value)),
+            Self::Absent => Observation::Absent,
+            Self::Unknown(error) => Observation::Unknown(error),
+        }
+    }
+
+    /// Whether both observations are known and differ, comparing observed values with `eq`.
+    pub fn differs_by(&self, other: &Self, eq: impl Fn(&T, &T) -> bool) -> bool {
+        self.is_known()
+            && other.is_known()
+            && match (self, other) {
+                (Self::Observed(a), Self::Observed(b)) => !eq(a, b),
+                (Self::Absent, Self::Absent) => false,
+                _ => true,
+            }
+    }
+
+    /// Replace with `new`, unless `new` is unknown and the current value is known.
+    pub fn update(&mut self, new: Self) {
+        if !(new.is_unknown() && self.is_known()) {
+            *self = new;
+        }
+    }
+}
+
+impl<T: PartialEq> Observation<T> {
+    /// Whether both observations are known and differ.
+    pub fn differs(&self, other: &Self) -> bool {
+        self.differs_by(other, T::eq)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Observation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotObserved => write!(f, "not observed"),
+            Self::Observed(value) => value.fmt(f),
+            Self::Absent => write!(f, "absent"),
+            Self::Unknown(error) => write!(f, "unknown ({})", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differs() {
+        let observed = Observation::Observed(1);
+        assert!(observed.differs(&Observation::Observed(2)));
+        assert!(observed.differs(&Observation::Absent));
+        assert!(!observed.differs(&Observation::Observed(1)));
+        assert!(!observed.differs(&Observation::Unknown("error".to_string())));
+        assert!(!observed.differs(&Observation::NotObserved));
+    }
+
+    #[test]
+    fn update() {
+        let mut observation = Observation::Observed(1);
+        observation.update(Observation::Unknown("error".to_string()));
+        assert_eq!(observation, Observation::Observed(1));
+        observation.update(Observation::Absent);
+        assert_eq!(observation, Observation::Absent);
+
+        let mut observation = Observation::Unknown("error".to_string());
+        observation.update(Observation::Observed(1));
+        assert_eq!(observation, Observation::Observed(1));
+    }
+
+    #[test]
+    fn serialize_tagged() {
+        let json = |observation: Observation<String>| serde_json::to_string(&observation).unwrap();
+        assert_eq!(
+            json(Observation::Observed("a".to_string())),
+            r#"{"observation":"Observed","value":"a"}"#
+        );
+        assert_eq!(json(Observation::Absent), r#"{"observation":"Absent"}"#);
+    }
+
+    #[test]
+    fn deserialize_legacy_option() {
+        let parse = |json: &str| serde_json::from_str::<Observation<String>>(json).unwrap();
+        for observation in [
+            Observation::NotObserved,
+            Observation::Observed("a".to_string()),
+            Observation::Absent,
+            Observation::Unknown("error".to_string()),
+        ] {
+            assert_eq!(
+                parse(&serde_json::to_string(&observation).unwrap()),
+                observation
+            );
+        }
+        assert_eq!(parse(r#""a""#), Observation::Observed("a".to_string()));
+        assert_eq!(parse("null"), Observation::Absent);
+        // legacy values named like a variant
+        assert_eq!(
+            parse(r#""Absent""#),
+            Observation::Observed("Absent".to_string())
+        );
+        assert_eq!(
+            parse(r#""NotObserved""#),
+            Observation::Observed("NotObserved".to_string())
+        );
+    }
+}
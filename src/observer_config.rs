@@ -1,21 +1,131 @@
 use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, ops::Deref, path::PathBuf, sync::Arc};
 
-use crate::{network_state::NetworkState, NetworkChange};
+use crate::{
+    addresses::Ipv6AddressFilter,
+    compare::{Comparator, ComparePolicy},
+    connectivity::ConnectivityCheck,
+    integrity::Integrity,
+    network_state::NetworkState,
+    state_store::{FileStore, StateStore},
+    statistics::StatisticsThresholds,
+    NetworkChange,
+};
+
+#[cfg(feature = "encryption")]
+use crate::encryption::EncryptionKey;
 
 pub const DEFAULT_EXPIRE_TIME: u64 = 3600;
 
+/// When the observer persists its state, in addition to when it is dropped or closed.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Autosave {
+    /// Only persist on drop, [`close`](crate::NetworkObserver::close) or
+    /// [`checkpoint`](crate::NetworkObserver::checkpoint).
+    #[default]
+    Off,
+    /// Persist after every detected change.
+    OnChange,
+    /// Persist on the first `state_change` call after the given number of seconds since the last save.
+    Interval(u64),
+}
+
 type OnChangeCallback = fn(change: &NetworkChange, old: &NetworkState, new: &NetworkState);
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// The callback called on changes, equal to the same function.
+#[derive(Debug, Clone, Copy)]
+pub struct OnChange(pub OnChangeCallback);
+
+impl PartialEq for OnChange {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+/// A store or comparator shared between configs, equal only to clones of itself.
+#[derive(Debug)]
+pub struct Shared<T: ?Sized>(pub Arc<T>);
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: ?Sized> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ObserverConfig {
     pub expire_time: u64,
     pub persist: bool,
+    #[serde(default)]
+    pub autosave: Autosave,
     pub observe_all_interfaces: bool,
     pub observe_public_address: bool,
     pub observe_default_route: bool,
     pub observe_all_routes: bool,
+    /// Fields of interfaces and routes that count toward change detection.
+    #[serde(default)]
+    pub compare_policy: ComparePolicy,
+    /// Replaces the compare policy when set.
+    #[serde(skip)]
+    pub comparator: Option<Shared<dyn Comparator>>,
+    /// IPv6 addresses of interfaces that are compared.
+    #[serde(default)]
+    pub ipv6_address_filter: Ipv6AddressFilter,
+    #[serde(default)]
+    pub observe_dns_config: bool,
+    #[serde(default)]
+    pub observe_gateway_mac: bool,
+    #[serde(default)]
+    pub observe_network_id: bool,
+    #[serde(default)]
+    pub observe_wireless: bool,
+    #[serde(default)]
+    pub observe_vpn: bool,
+    #[serde(default)]
+    pub observe_routing_rules: bool,
+    /// Connectivity is observed when a check is set.
+    #[serde(default)]
+    pub connectivity_check: Option<ConnectivityCheck>,
+    /// Interface traffic counters are observed when thresholds are set.
+    #[serde(default)]
+    pub statistics_thresholds: Option<StatisticsThresholds>,
+    /// Destinations whose selected route is watched, all routes are observed when set.
+    #[serde(default)]
+    pub watched_destinations: Vec<IpAddr>,
+    /// Interfaces of these link kinds, such as `veth`, are not observed.
+    #[serde(default)]
+    pub ignored_link_kinds: Vec<String>,
+    /// Network namespace the probes run in, the namespace of the process when `None`.
+    #[serde(default)]
+    pub netns: Option<PathBuf>,
+    #[serde(skip)]
+    pub on_change: Option<OnChange>,
     #[serde(skip)]
-    pub on_change: Option<OnChangeCallback>,
+    pub store: Option<Shared<dyn StateStore>>,
+    #[serde(skip)]
+    pub known_networks_store: Option<Shared<dyn StateStore>>,
+    #[serde(skip)]
+    pub integrity: Integrity,
+    #[cfg(feature = "encryption")]
+    #[serde(skip)]
+    pub encryption_key: EncryptionKey,
+    #[cfg(feature = "encryption")]
+    #[serde(skip)]
+    pub previous_encryption_keys: Vec<EncryptionKey>,
 }
 
 impl Default for ObserverConfig {
@@ -23,11 +133,33 @@ impl Default for ObserverConfig {
         Self {
             expire_time: DEFAULT_EXPIRE_TIME,
             persist: false,
+            autosave: Autosave::Off,
             observe_all_interfaces: false,
             observe_public_address: false,
             observe_default_route: false,
             observe_all_routes: false,
+            compare_policy: ComparePolicy::default(),
+            comparator: None,
+            ipv6_address_filter: Ipv6AddressFilter::All,
+            observe_dns_config: false,
+            observe_gateway_mac: false,
+            observe_network_id: false,
+            observe_wireless: false,
+            observe_vpn: false,
+            observe_routing_rules: false,
+            connectivity_check: None,
+            statistics_thresholds: None,
+            watched_destinations: Vec::new(),
+            ignored_link_kinds: Vec::new(),
+            netns: None,
             on_change: None,
+            store: None,
+            known_networks_store: None,
+            integrity: Integrity::None,
+            #[cfg(feature = "encryption")]
+            encryption_key: EncryptionKey::default(),
+            #[cfg(feature = "encryption")]
+            previous_encryption_keys: Vec::new(),
         }
     }
 }
@@ -44,11 +176,33 @@ impl ObserverConfig {
         Self {
             expire_time,
             persist,
+            autosave: Autosave::Off,
             observe_all_interfaces,
             observe_public_address,
             observe_default_route,
             observe_all_routes,
+            compare_policy: ComparePolicy::default(),
+            comparator: None,
+            ipv6_address_filter: Ipv6AddressFilter::All,
+            observe_dns_config: false,
+            observe_gateway_mac: false,
+            observe_network_id: false,
+            observe_wireless: false,
+            observe_vpn: false,
+            observe_routing_rules: false,
+            connectivity_check: None,
+            statistics_thresholds: None,
+            watched_destinations: Vec::new(),
+            ignored_link_kinds: Vec::new(),
+            netns: None,
             on_change: None,
+            store: None,
+            known_networks_store: None,
+            integrity: Integrity::None,
+            #[cfg(feature = "encryption")]
+            encryption_key: EncryptionKey::default(),
+            #[cfg(feature = "encryption")]
+            previous_encryption_keys: Vec::new(),
         }
     }
 
@@ -72,20 +226,183 @@ impl ObserverConfig {
         self
     }
 
+    /// Select the fields of interfaces and routes that count toward change detection.
+    pub fn set_compare_policy(mut self, policy: ComparePolicy) -> Self {
+        self.compare_policy = policy;
+        self
+    }
+
+    /// Compare interfaces and routes with `comparator` instead of the compare policy.
+    pub fn set_comparator(mut self, comparator: impl Comparator + 'static) -> Self {
+        self.comparator = Some(Shared(Arc::new(comparator)));
+        self
+    }
+
+    /// The configured comparator, or the compare policy.
+    pub fn comparator(&self) -> &dyn Comparator {
+        match &self.comparator {
+            Some(comparator) => &**comparator,
+            None => &self.compare_policy,
+        }
+    }
+
+    /// Ignore temporary IPv6 addresses or compare only IPv6 prefixes, so that privacy
+    /// extension address rotation is not reported as an interface change.
+    pub fn set_ipv6_address_filter(mut self, filter: Ipv6AddressFilter) -> Self {
+        self.ipv6_address_filter = filter;
+        self
+    }
+
+    pub fn enable_observe_dns_config(mut self, observe_dns_config: bool) -> Self {
+        self.observe_dns_config = observe_dns_config;
+        self
+    }
+
+    /// Observe the default gateway's link-layer address to tell apart networks sharing a gateway IP.
+    pub fn enable_observe_gateway_mac(mut self, observe_gateway_mac: bool) -> Self {
+        self.observe_gateway_mac = observe_gateway_mac;
+        self
+    }
+
+    /// Observe the [`NetworkId`](crate::network_id::NetworkId) and report joining known or unknown networks.
+    pub fn enable_observe_network_id(mut self, observe_network_id: bool) -> Self {
+        self.observe_network_id = observe_network_id;
+        self
+    }
+
+    /// Observe the SSID and access point of wireless interfaces, Linux only.
+    pub fn enable_observe_wireless(mut self, observe_wireless: bool) -> Self {
+        self.observe_wireless = observe_wireless;
+        self
+    }
+
+    /// Detect tunnel interfaces that the default route or split-tunnel routes point at.
+    pub fn enable_observe_vpn(mut self, observe_vpn: bool) -> Self {
+        self.observe_vpn = observe_vpn;
+        self
+    }
+
+    /// Observe the policy routing rules of both address families, Linux only.
+    pub fn enable_observe_routing_rules(mut self, observe_routing_rules: bool) -> Self {
+        self.observe_routing_rules = observe_routing_rules;
+        self
+    }
+
+    /// Set the backend used to persist the known networks, when persistence is enabled.
+    pub fn set_known_networks_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.known_networks_store = Some(Shared(Arc::new(store)));
+        self
+    }
+
+    /// The configured known networks store, or one alongside the configured state store.
+    ///
+    /// `None` if the state store has no sibling, such as a [`MemoryStore`](crate::state_store::MemoryStore);
+    /// the known networks are then not persisted.
+    pub fn known_networks_store(&self) -> Option<Arc<dyn StateStore>> {
+        self.known_networks_store
+            .as_ref()
+            .map(|store| store.0.clone())
+            .or_else(|| self.store().sibling("known_networks.cache"))
+    }
+
+    /// Set the check used to observe internet connectivity and captive portals.
+    pub fn set_connectivity_check(mut self, check: ConnectivityCheck) -> Self {
+        self.connectivity_check = Some(check);
+        self
+    }
+
+    /// Observe interface traffic counters, reporting when their rates cross `thresholds`.
+    pub fn set_statistics_thresholds(mut self, thresholds: StatisticsThresholds) -> Self {
+        self.statistics_thresholds = Some(thresholds);
+        self
+    }
+
+    /// Report when the route selected to reach any of `destinations` changes.
+    pub fn set_watched_destinations(
+        mut self,
+        destinations: impl IntoIterator<Item = IpAddr>,
+    ) -> Self {
+        self.watched_destinations = destinations.into_iter().collect();
+        self
+    }
+
+    /// Ignore interfaces whose link kind, such as `veth` or `bridge`, is one of `kinds`.
+    pub fn set_ignored_link_kinds(
+        mut self,
+        kinds: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.ignored_link_kinds = kinds.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Observe the network namespace at `path`, such as `/var/run/netns/foo` or
+    /// `/proc/<pid>/ns/net`, Linux only.
+    ///
+    /// The probes run on a thread that enters the namespace, the rest of the process is not
+    /// affected. Wireless interfaces and the DNS configuration are read from sysfs and files
+    /// of the host and stay unknown. Link speed and duplex and the gateway, type, speeds
+    /// and DNS servers of interfaces are not reported.
+    pub fn set_netns(mut self, path: impl Into<PathBuf>) -> Self {
+        self.netns = Some(path.into());
+        self
+    }
+
     pub fn enable_persist(mut self, persist: bool) -> Self {
         self.persist = persist;
         self
     }
 
+    /// Set when the state is persisted while observing, requires persistence to be enabled.
+    pub fn set_autosave(mut self, autosave: Autosave) -> Self {
+        self.autosave = autosave;
+        self
+    }
+
     pub fn set_expire_time(mut self, expire_time: u64) -> Self {
         self.expire_time = expire_time;
         self
     }
 
     pub fn set_on_change(mut self, callback: OnChangeCallback) -> Self {
-        self.on_change = Some(callback);
+        self.on_change = Some(OnChange(callback));
         self
     }
+
+    /// Set the backend used to persist the state, when persistence is enabled.
+    pub fn set_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.store = Some(Shared(Arc::new(store)));
+        self
+    }
+
+    /// Set the integrity trailer appended to the persisted state.
+    pub fn set_integrity(mut self, integrity: Integrity) -> Self {
+        self.integrity = integrity;
+        self
+    }
+
+    /// Set the key used to encrypt the persisted state.
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.encryption_key = key;
+        self
+    }
+
+    /// Set keys to try when the persisted state can't be decrypted with the current key.
+    ///
+    /// State loaded with one of these keys is re-encrypted with the current key.
+    #[cfg(feature = "encryption")]
+    pub fn set_previous_encryption_keys(mut self, keys: Vec<EncryptionKey>) -> Self {
+        self.previous_encryption_keys = keys;
+        self
+    }
+
+    /// The configured state store, or the default file store.
+    pub fn store(&self) -> Arc<dyn StateStore> {
+        match &self.store {
+            Some(store) => store.0.clone(),
+            None => Arc::new(FileStore::default()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -104,4 +421,24 @@ mod tests {
             .enable_persist(true);
         assert_eq!(config_new, config_set);
     }
+
+    #[test]
+    fn shared_fields_compare_by_identity() {
+        use crate::state_store::MemoryStore;
+
+        let config = ObserverConfig::default().set_store(MemoryStore::new());
+        assert_eq!(config, config.clone());
+        assert_ne!(config, config.clone().set_store(MemoryStore::new()));
+        assert_ne!(config, ObserverConfig::default());
+    }
+
+    #[test]
+    fn known_networks_store_follows_store() {
+        use crate::state_store::MemoryStore;
+
+        let config = ObserverConfig::default().set_store(MemoryStore::new());
+        assert!(config.known_networks_store().is_none());
+        let config = config.set_known_networks_store(MemoryStore::new());
+        assert!(config.known_networks_store().is_some());
+    }
 }
@@ -0,0 +1,31 @@
+use crate::error::Result;
+use log::debug;
+use public_ip_address::lookup::{LookupProvider, Parameters};
+use std::net::IpAddr;
+
+fn providers() -> Vec<(LookupProvider, Option<Parameters>)> {
+    vec![
+        (LookupProvider::MyIpCom, None),
+        (LookupProvider::GetJsonIp, None),
+        (LookupProvider::Ipify, None),
+        (LookupProvider::IpInfo, None),
+    ]
+}
+
+/// Look up the public IP address, cached for 10 seconds.
+#[maybe_async::maybe_async]
+pub async fn get_public_address() -> Result<IpAddr> {
+    let response =
+        public_ip_address::perform_cached_lookup_with(providers(), None, Some(10), false).await?;
+    debug!("Public address: {}", response.ip);
+    Ok(response.ip)
+}
+
+/// Look up the public IP address without the on-disk cache, which does not tell network
+/// namespaces apart.
+#[maybe_async::maybe_async]
+pub async fn lookup_public_address() -> Result<IpAddr> {
+    let response = public_ip_address::perform_lookup_with(providers(), None).await?;
+    debug!("Public address: {}", response.ip);
+    Ok(response.ip)
+}
@@ -1,9 +1,19 @@
-use log::{debug, warn};
+use crate::error::{Error, Result};
+use log::debug;
 use net_route::Route as NetRoute;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::{
+    cmp::Reverse,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::Deref,
+    str::FromStr,
+};
 
 /// Contains information that describes a route in the local computer's Ipv4 or Ipv6 routing table.
+///
+/// Its [`Display`](fmt::Display) form names the interface by index, such as `dev 2`, which is
+/// stable across network namespaces; [`Route::display_named`] resolves the name instead.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Route {
     /// Network address of the destination. `0.0.0.0` with a prefix of `0` is considered a default route.
@@ -21,6 +31,230 @@ pub struct Route {
     ///
     /// On macOS, this must be `Some` if gateway is `None`
     pub ifindex: Option<u32>,
+
+    /// The route priority, lower is preferred. Linux and Windows only.
+    #[serde(default)]
+    pub metric: Option<u32>,
+
+    /// The policy routing table the route belongs to. Linux only.
+    #[serde(default)]
+    pub table: Option<u32>,
+
+    /// What installed the route. Linux only.
+    #[serde(default)]
+    pub protocol: Option<RouteProtocol>,
+
+    /// The distance to the destination. Linux only.
+    #[serde(default)]
+    pub scope: Option<RouteScope>,
+
+    /// The preferred source address for traffic using the route. Linux only.
+    #[serde(default)]
+    pub source: Option<IpAddr>,
+
+    /// What the route does with matching traffic, `None` where unknown. Linux only.
+    #[serde(default)]
+    pub kind: Option<RouteType>,
+
+    /// The next hops of a multipath route, whose own gateway and interface are then `None`.
+    /// Linux only.
+    #[serde(default)]
+    pub nexthops: Vec<NextHop>,
+}
+
+/// A next hop of a multipath route.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NextHop {
+    pub gateway: Option<IpAddr>,
+    pub ifindex: Option<u32>,
+    /// Relative share of the traffic, at least 1.
+    pub weight: u16,
+}
+
+/// What a route does with matching traffic, as the type in `ip route`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteType {
+    Unicast,
+    Local,
+    Broadcast,
+    Anycast,
+    Multicast,
+    Blackhole,
+    Unreachable,
+    Prohibit,
+    Throw,
+    Other(u8),
+}
+
+impl fmt::Display for RouteType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unicast => write!(f, "unicast"),
+            Self::Local => write!(f, "local"),
+            Self::Broadcast => write!(f, "broadcast"),
+            Self::Anycast => write!(f, "anycast"),
+            Self::Multicast => write!(f, "multicast"),
+            Self::Blackhole => write!(f, "blackhole"),
+            Self::Unreachable => write!(f, "unreachable"),
+            Self::Prohibit => write!(f, "prohibit"),
+            Self::Throw => write!(f, "throw"),
+            Self::Other(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+impl FromStr for RouteType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "unicast" => Ok(Self::Unicast),
+            "local" => Ok(Self::Local),
+            "broadcast" => Ok(Self::Broadcast),
+            "anycast" => Ok(Self::Anycast),
+            "multicast" => Ok(Self::Multicast),
+            "blackhole" => Ok(Self::Blackhole),
+            "unreachable" => Ok(Self::Unreachable),
+            "prohibit" => Ok(Self::Prohibit),
+            "throw" => Ok(Self::Throw),
+            s => Err(Error::InvalidRoute(format!("unknown route type {}", s))),
+        }
+    }
+}
+
+impl From<u8> for RouteType {
+    fn from(kind: u8) -> Self {
+        match kind {
+            1 => Self::Unicast,
+            2 => Self::Local,
+            3 => Self::Broadcast,
+            4 => Self::Anycast,
+            5 => Self::Multicast,
+            6 => Self::Blackhole,
+            7 => Self::Unreachable,
+            8 => Self::Prohibit,
+            9 => Self::Throw,
+            kind => Self::Other(kind),
+        }
+    }
+}
+
+/// What installed a route, as `proto` in `ip route`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteProtocol {
+    Unspec,
+    Redirect,
+    Kernel,
+    Boot,
+    Static,
+    Ra,
+    Dhcp,
+    Other(u8),
+}
+
+impl fmt::Display for RouteProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unspec => write!(f, "unspec"),
+            Self::Redirect => write!(f, "redirect"),
+            Self::Kernel => write!(f, "kernel"),
+            Self::Boot => write!(f, "boot"),
+            Self::Static => write!(f, "static"),
+            Self::Ra => write!(f, "ra"),
+            Self::Dhcp => write!(f, "dhcp"),
+            Self::Other(protocol) => write!(f, "{}", protocol),
+        }
+    }
+}
+
+impl FromStr for RouteProtocol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "unspec" => Ok(Self::Unspec),
+            "redirect" => Ok(Self::Redirect),
+            "kernel" => Ok(Self::Kernel),
+            "boot" => Ok(Self::Boot),
+            "static" => Ok(Self::Static),
+            "ra" => Ok(Self::Ra),
+            "dhcp" => Ok(Self::Dhcp),
+            s => s
+                .parse::<u8>()
+                .map(Self::from)
+                .map_err(|_| Error::InvalidRoute(format!("unknown protocol {}", s))),
+        }
+    }
+}
+
+impl From<u8> for RouteProtocol {
+    fn from(protocol: u8) -> Self {
+        match protocol {
+            0 => Self::Unspec,
+            1 => Self::Redirect,
+            2 => Self::Kernel,
+            3 => Self::Boot,
+            4 => Self::Static,
+            9 => Self::Ra,
+            16 => Self::Dhcp,
+            protocol => Self::Other(protocol),
+        }
+    }
+}
+
+/// The distance to the destination of a route, as `scope` in `ip route`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteScope {
+    Universe,
+    Site,
+    Link,
+    Host,
+    Nowhere,
+    Other(u8),
+}
+
+impl fmt::Display for RouteScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Universe => write!(f, "global"),
+            Self::Site => write!(f, "site"),
+            Self::Link => write!(f, "link"),
+            Self::Host => write!(f, "host"),
+            Self::Nowhere => write!(f, "nowhere"),
+            Self::Other(scope) => write!(f, "{}", scope),
+        }
+    }
+}
+
+impl FromStr for RouteScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "global" | "universe" => Ok(Self::Universe),
+            "site" => Ok(Self::Site),
+            "link" => Ok(Self::Link),
+            "host" => Ok(Self::Host),
+            "nowhere" => Ok(Self::Nowhere),
+            s => s
+                .parse::<u8>()
+                .map(Self::from)
+                .map_err(|_| Error::InvalidRoute(format!("unknown scope {}", s))),
+        }
+    }
+}
+
+impl From<u8> for RouteScope {
+    fn from(scope: u8) -> Self {
+        match scope {
+            0 => Self::Universe,
+            200 => Self::Site,
+            253 => Self::Link,
+            254 => Self::Host,
+            255 => Self::Nowhere,
+            scope => Self::Other(scope),
+        }
+    }
 }
 
 impl Default for Route {
@@ -30,6 +264,13 @@ impl Default for Route {
             prefix: 0,
             gateway: None,
             ifindex: None,
+            metric: None,
+            table: None,
+            protocol: None,
+            scope: None,
+            source: None,
+            kind: None,
+            nexthops: Vec::new(),
         }
     }
 }
@@ -47,20 +288,411 @@ impl Route {
             prefix,
             gateway,
             ifindex,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    pub fn with_scope(mut self, scope: RouteScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    pub fn with_source(mut self, source: IpAddr) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_kind(mut self, kind: RouteType) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_nexthops(mut self, nexthops: Vec<NextHop>) -> Self {
+        self.nexthops = nexthops;
+        self
+    }
+
+    /// Whether the route forwards traffic, also when its type is unknown.
+    pub fn is_unicast(&self) -> bool {
+        self.kind.is_none_or(|kind| kind == RouteType::Unicast)
+    }
+
+    /// Gateways and interfaces of the route, those of its next hops if it is multipath.
+    pub fn hops(&self) -> Vec<(Option<IpAddr>, Option<u32>)> {
+        if self.nexthops.is_empty() {
+            return vec![(self.gateway, self.ifindex)];
         }
+        self.nexthops
+            .iter()
+            .map(|nexthop| (nexthop.gateway, nexthop.ifindex))
+            .collect()
     }
 
     /// Get the netmask covering the network portion of the destination address.
+    ///
+    /// A prefix longer than the address yields the full mask.
     pub fn mask(&self) -> IpAddr {
+        let prefix = self.prefix as u32;
         match self.destination {
             IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(
-                u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0),
+                32u32
+                    .checked_sub(prefix)
+                    .map_or(u32::MAX, |bits| u32::MAX.checked_shl(bits).unwrap_or(0)),
             )),
             IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(
-                u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0),
+                128u32
+                    .checked_sub(prefix)
+                    .map_or(u128::MAX, |bits| u128::MAX.checked_shl(bits).unwrap_or(0)),
             )),
         }
     }
+
+    /// Whether `ip` is in the destination network of the route.
+    ///
+    /// A route whose prefix is longer than its address contains nothing.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.destination, ip, self.mask()) {
+            (IpAddr::V4(destination), IpAddr::V4(ip), IpAddr::V4(mask)) if self.prefix <= 32 => {
+                u32::from(destination) & u32::from(mask) == u32::from(ip) & u32::from(mask)
+            }
+            (IpAddr::V6(destination), IpAddr::V6(ip), IpAddr::V6(mask)) if self.prefix <= 128 => {
+                u128::from(destination) & u128::from(mask) == u128::from(ip) & u128::from(mask)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The name of the interface with index `ifindex`, in the network namespace of the calling thread.
+#[cfg(target_os = "linux")]
+pub(crate) fn interface_name(ifindex: u32) -> Option<String> {
+    let mut name = [0; libc::IF_NAMESIZE];
+    // SAFETY: the buffer holds IF_NAMESIZE bytes as required
+    let name = unsafe { libc::if_indextoname(ifindex, name.as_mut_ptr()) };
+    if name.is_null() {
+        return None;
+    }
+    // SAFETY: on success the buffer holds a NUL terminated name
+    let name = unsafe { std::ffi::CStr::from_ptr(name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// The index of the interface named `name`, in the network namespace of the calling thread.
+#[cfg(target_os = "linux")]
+pub(crate) fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: the name is a valid C string for the duration of the call
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn interface_name(ifindex: u32) -> Option<String> {
+    netdev::get_interfaces()
+        .into_iter()
+        .find(|interface| interface.index == ifindex)
+        .map(|interface| interface.name)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn interface_index(name: &str) -> Option<u32> {
+    netdev::get_interfaces()
+        .into_iter()
+        .find(|interface| interface.name == name)
+        .map(|interface| interface.index)
+}
+
+fn table_name(table: u32) -> Option<&'static str> {
+    match table {
+        253 => Some("default"),
+        MAIN_TABLE => Some("main"),
+        LOCAL_TABLE => Some("local"),
+        _ => None,
+    }
+}
+
+impl Route {
+    /// The destination in CIDR notation, such as `10.0.0.0/8`.
+    pub fn cidr(&self) -> String {
+        format!("{}/{}", self.destination, self.prefix)
+    }
+
+    /// A route to the destination in CIDR notation, a host route without a prefix.
+    ///
+    /// Like `ip route`, a destination with bits set beyond the prefix is rejected.
+    pub fn from_cidr(cidr: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidRoute(format!("{}: {}", reason, cidr));
+        let (destination, prefix) = match cidr.split_once('/') {
+            Some((destination, prefix)) => (destination, Some(prefix)),
+            None => (cidr, None),
+        };
+        let destination: IpAddr = destination
+            .parse()
+            .map_err(|_| invalid(format!("invalid address {}", destination)))?;
+        let max_prefix = if destination.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| invalid(format!("invalid prefix length {}", prefix)))?,
+            None => max_prefix,
+        };
+        let route = Self::new(destination, prefix, None, None);
+        let network = match (destination, route.mask()) {
+            (IpAddr::V4(destination), IpAddr::V4(mask)) => {
+                IpAddr::from(Ipv4Addr::from(u32::from(destination) & u32::from(mask)))
+            }
+            (IpAddr::V6(destination), IpAddr::V6(mask)) => {
+                IpAddr::from(Ipv6Addr::from(u128::from(destination) & u128::from(mask)))
+            }
+            _ => destination,
+        };
+        if network != destination {
+            return Err(invalid(format!(
+                "host bits set, the network is {}",
+                network
+            )));
+        }
+        Ok(route)
+    }
+
+    /// Formats the route like [`Display`](fmt::Display), naming the interface if its index
+    /// resolves in the network namespace of the calling thread.
+    pub fn display_named(&self) -> impl fmt::Display + '_ {
+        Named(self)
+    }
+
+    /// Parses a route like [`FromStr`], also accepting an interface name for `dev` that is
+    /// resolved in the network namespace of the calling thread.
+    pub fn parse_named(s: &str) -> Result<Self> {
+        Self::parse(s, interface_index)
+    }
+
+    fn fmt_with(
+        &self,
+        f: &mut fmt::Formatter,
+        interface_name: impl Fn(u32) -> Option<String>,
+    ) -> fmt::Result {
+        // an IPv6 default route is only recognisable by its addresses
+        let is_default = self.prefix == 0
+            && self.destination.is_unspecified()
+            && (self.destination.is_ipv4()
+                || self.hops().iter().any(|(gateway, _)| gateway.is_some())
+                || self.source.is_some());
+        if let Some(kind) = self.kind.filter(|kind| *kind != RouteType::Unicast) {
+            write!(f, "{} ", kind)?;
+        }
+        if is_default {
+            write!(f, "default")?;
+        } else {
+            write!(f, "{}", self.cidr())?;
+        }
+        if let Some(gateway) = self.gateway {
+            write!(f, " via {}", gateway)?;
+        }
+        if let Some(ifindex) = self.ifindex {
+            match interface_name(ifindex) {
+                Some(name) => write!(f, " dev {}", name)?,
+                None => write!(f, " dev {}", ifindex)?,
+            }
+        }
+        if let Some(table) = self.table {
+            match table_name(table) {
+                Some(name) => write!(f, " table {}", name)?,
+                None => write!(f, " table {}", table)?,
+            }
+        }
+        if let Some(protocol) = self.protocol {
+            write!(f, " proto {}", protocol)?;
+        }
+        if let Some(scope) = self.scope {
+            write!(f, " scope {}", scope)?;
+        }
+        if let Some(source) = self.source {
+            write!(f, " src {}", source)?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {}", metric)?;
+        }
+        for nexthop in &self.nexthops {
+            write!(f, " nexthop")?;
+            if let Some(gateway) = nexthop.gateway {
+                write!(f, " via {}", gateway)?;
+            }
+            if let Some(ifindex) = nexthop.ifindex {
+                match interface_name(ifindex) {
+                    Some(name) => write!(f, " dev {}", name)?,
+                    None => write!(f, " dev {}", ifindex)?,
+                }
+            }
+            write!(f, " weight {}", nexthop.weight)?;
+        }
+        Ok(())
+    }
+
+    fn parse(s: &str, interface_index: impl Fn(&str) -> Option<u32>) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidRoute(format!("{}: {}", reason, s));
+        let address = |value: &str| {
+            value
+                .parse::<IpAddr>()
+                .map_err(|_| invalid(format!("invalid address {}", value)))
+        };
+        let number = |value: &str| {
+            value
+                .parse::<u32>()
+                .map_err(|_| invalid(format!("invalid number {}", value)))
+        };
+
+        let mut words = s.split_whitespace().peekable();
+        // the type precedes the destination, unicast when omitted
+        let kind = match words.peek().map(|word| word.parse::<RouteType>()) {
+            Some(Ok(kind)) => {
+                words.next();
+                Some(kind)
+            }
+            _ => None,
+        };
+        let destination = words.next().ok_or_else(|| invalid("empty".to_string()))?;
+        let default = destination == "default";
+        let mut route = if default {
+            Route::default()
+        } else {
+            Route::from_cidr(destination)?
+        };
+        route.kind = kind;
+        let ifindex = |value: &str| match value.parse() {
+            Ok(ifindex) => Ok(ifindex),
+            Err(_) => interface_index(value)
+                .ok_or_else(|| invalid(format!("unknown interface {}", value))),
+        };
+        while let Some(keyword) = words.next() {
+            if keyword == "nexthop" {
+                route.nexthops.push(NextHop {
+                    gateway: None,
+                    ifindex: None,
+                    weight: 1,
+                });
+                continue;
+            }
+            let value = words
+                .next()
+                .ok_or_else(|| invalid(format!("missing value of {}", keyword)))?;
+            // the next hops come last
+            if let Some(nexthop) = route.nexthops.last_mut() {
+                match keyword {
+                    "via" => nexthop.gateway = Some(address(value)?),
+                    "dev" => nexthop.ifindex = Some(ifindex(value)?),
+                    "weight" => {
+                        nexthop.weight = value
+                            .parse()
+                            .ok()
+                            .filter(|weight| *weight > 0)
+                            .ok_or_else(|| invalid(format!("invalid weight {}", value)))?
+                    }
+                    keyword => return Err(invalid(format!("unknown keyword {}", keyword))),
+                }
+                continue;
+            }
+            match keyword {
+                "via" => route.gateway = Some(address(value)?),
+                "dev" => route.ifindex = Some(ifindex(value)?),
+                "table" => {
+                    route.table = Some(match value {
+                        "default" => 253,
+                        "main" => MAIN_TABLE,
+                        "local" => LOCAL_TABLE,
+                        value => number(value)?,
+                    })
+                }
+                "proto" => route.protocol = Some(value.parse()?),
+                "scope" => route.scope = Some(value.parse()?),
+                "src" => route.source = Some(address(value)?),
+                "metric" => route.metric = Some(number(value)?),
+                keyword => return Err(invalid(format!("unknown keyword {}", keyword))),
+            }
+        }
+        let first_gateway = route
+            .gateway
+            .or(route.nexthops.first().and_then(|hop| hop.gateway));
+        if default && first_gateway.or(route.source).is_some_and(|a| a.is_ipv6()) {
+            route.destination = Ipv6Addr::UNSPECIFIED.into();
+        }
+        let family = route.destination.is_ipv4();
+        if route
+            .gateway
+            .iter()
+            .chain(route.nexthops.iter().filter_map(|hop| hop.gateway.as_ref()))
+            .chain(&route.source)
+            .any(|a| a.is_ipv4() != family)
+        {
+            return Err(invalid("mixed address families".to_string()));
+        }
+        Ok(route)
+    }
+}
+
+/// Formats the route in `ip route` style, such as `10.0.0.0/8 via 192.168.1.1 dev 2`.
+///
+/// The interface is given by index, see [`Route::display_named`] for names.
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(f, |_| None)
+    }
+}
+
+struct Named<'a>(&'a Route);
+
+impl fmt::Display for Named<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_with(f, interface_name)
+    }
+}
+
+/// Parses a route in `ip route` style, the destination in CIDR notation or `default`.
+///
+/// A destination without a prefix is a host route. `dev` takes an interface index,
+/// see [`Route::parse_named`] for names.
+impl FromStr for Route {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, |_| None)
+    }
+}
+
+/// Serde functions for a route in its compact string form, use with
+/// `#[serde(with = "network_changed::routes::compact")]`.
+///
+/// The interface is stored by index, so the form does not depend on the local interface names.
+pub mod compact {
+    use super::Route;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(route: &Route, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(route)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Route, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
 }
 
 impl From<NetRoute> for Route {
@@ -70,40 +702,537 @@ impl From<NetRoute> for Route {
             prefix: route.prefix,
             gateway: route.gateway,
             ifindex: route.ifindex,
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            metric: route.metric,
+            #[cfg(target_os = "linux")]
+            table: Some(route.table.into()),
+            #[cfg(target_os = "linux")]
+            source: route.source_hint,
+            ..Default::default()
+        }
+    }
+}
+
+/// The main routing table on Linux.
+pub const MAIN_TABLE: u32 = 254;
+
+/// The table of local and broadcast routes.
+pub const LOCAL_TABLE: u32 = 255;
+
+/// Routes of all tables, as captured from the system.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct RoutingTable(pub Vec<Route>);
+
+impl RoutingTable {
+    pub fn new(routes: Vec<Route>) -> Self {
+        Self(routes)
+    }
+
+    /// The unicast route of the main table used to reach `ip`, the longest matching prefix with
+    /// the lowest metric. Policy routing rules are not evaluated.
+    pub fn lookup(&self, ip: IpAddr) -> Option<&Route> {
+        self.0
+            .iter()
+            .filter(|route| route.table.is_none_or(|table| table == MAIN_TABLE))
+            .filter(|route| route.is_unicast() && route.contains(ip))
+            .min_by_key(|route| (Reverse(route.prefix), route.metric.unwrap_or_default()))
+    }
+}
+
+impl Deref for RoutingTable {
+    type Target = [Route];
+
+    fn deref(&self) -> &[Route] {
+        &self.0
+    }
+}
+
+impl From<Vec<Route>> for RoutingTable {
+    fn from(routes: Vec<Route>) -> Self {
+        Self(routes)
+    }
+}
+
+/// Default routes of each address family, sorted by metric.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefaultRoutes {
+    pub ipv4: Vec<Route>,
+    pub ipv6: Vec<Route>,
+}
+
+impl DefaultRoutes {
+    /// Collect the unicast default routes of the main table from a routing table.
+    pub fn new(routes: Vec<Route>) -> Self {
+        let mut default_routes = Self::default();
+        for route in routes {
+            if route.prefix != 0
+                || !route.is_unicast()
+                || !route.destination.is_unspecified()
+                || route
+                    .gateway
+                    .is_some_and(|gateway| gateway.is_unspecified())
+                || route.table.is_some_and(|table| table != MAIN_TABLE)
+            {
+                continue;
+            }
+            match route.destination {
+                IpAddr::V4(_) => default_routes.ipv4.push(route),
+                IpAddr::V6(_) => default_routes.ipv6.push(route),
+            }
         }
+        default_routes
+            .ipv4
+            .sort_by_key(|route| route.metric.unwrap_or_default());
+        default_routes
+            .ipv6
+            .sort_by_key(|route| route.metric.unwrap_or_default());
+        default_routes
+    }
+
+    /// The IPv4 default route with the lowest metric.
+    pub fn ipv4(&self) -> Option<&Route> {
+        self.ipv4.first()
+    }
+
+    /// The IPv6 default route with the lowest metric.
+    pub fn ipv6(&self) -> Option<&Route> {
+        self.ipv6.first()
+    }
+
+    /// The IPv4 default route, otherwise the IPv6 one.
+    pub fn preferred(&self) -> Option<&Route> {
+        self.ipv4().or(self.ipv6())
     }
 }
 
-#[maybe_async::maybe_async]
-pub async fn get_default_route() -> Option<Route> {
-    if let Ok(handle) = net_route::Handle::new() {
-        let default_route = handle.default_route().await;
+#[cfg(target_os = "linux")]
+fn route_from_message(message: netlink_packet_route::route::RouteMessage) -> Option<Route> {
+    use netlink_packet_route::{
+        route::{RouteAddress, RouteAttribute, RouteNextHop},
+        AddressFamily,
+    };
 
-        if let Ok(route) = default_route {
-            if let Some(route) = route {
-                debug!("Default route:\n{:?}", route);
-                return Some(route.into());
+    let address = |address: RouteAddress| match address {
+        RouteAddress::Inet(address) => Some(IpAddr::V4(address)),
+        RouteAddress::Inet6(address) => Some(IpAddr::V6(address)),
+        _ => None,
+    };
+    let header = message.header;
+    let mut route = Route {
+        // the default route has no destination attribute
+        destination: match header.address_family {
+            AddressFamily::Inet => Ipv4Addr::UNSPECIFIED.into(),
+            AddressFamily::Inet6 => Ipv6Addr::UNSPECIFIED.into(),
+            _ => return None,
+        },
+        prefix: header.destination_prefix_length,
+        table: Some(header.table.into()),
+        protocol: Some(u8::from(header.protocol).into()),
+        scope: Some(u8::from(header.scope).into()),
+        kind: Some(u8::from(header.kind))
+            .filter(|kind| *kind != 0)
+            .map(RouteType::from),
+        ..Default::default()
+    };
+    let nexthop = |nexthop: RouteNextHop| NextHop {
+        gateway: nexthop
+            .attributes
+            .into_iter()
+            .find_map(|attribute| match attribute {
+                RouteAttribute::Gateway(a) => address(a),
+                _ => None,
+            }),
+        ifindex: Some(nexthop.interface_index).filter(|ifindex| *ifindex != 0),
+        weight: u16::from(nexthop.hops) + 1,
+    };
+    for attribute in message.attributes {
+        match attribute {
+            RouteAttribute::Destination(a) => route.destination = address(a)?,
+            RouteAttribute::Gateway(a) => route.gateway = address(a),
+            RouteAttribute::PrefSource(a) => route.source = address(a),
+            RouteAttribute::Oif(ifindex) => route.ifindex = Some(ifindex),
+            RouteAttribute::Priority(metric) => route.metric = Some(metric),
+            // tables above 255 only fit the attribute
+            RouteAttribute::Table(table) => route.table = Some(table),
+            RouteAttribute::MultiPath(nexthops) => {
+                route.nexthops = nexthops.into_iter().map(nexthop).collect()
             }
+            _ => {}
         }
-    } else {
-        warn!("Failed to get route handle");
     }
-    warn!("No default route");
-    None
+    Some(route)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) async fn all_routes() -> Result<Vec<Route>> {
+    use futures::TryStreamExt;
+    use netlink_packet_route::{route::RouteMessage, AddressFamily};
+
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+    let mut routes = Vec::new();
+    for family in [AddressFamily::Inet, AddressFamily::Inet6] {
+        let mut message = RouteMessage::default();
+        message.header.address_family = family;
+        let mut messages = handle.route().get(message).execute();
+        while let Some(message) = messages
+            .try_next()
+            .await
+            .map_err(|e| Error::NetlinkError(e.to_string()))?
+        {
+            routes.extend(route_from_message(message));
+        }
+    }
+    debug!(
+        "All routes:\n{}",
+        routes
+            .iter()
+            .map(|route| route.display_named().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    Ok(routes)
+}
+
+pub(crate) async fn default_routes() -> Result<DefaultRoutes> {
+    let default_routes = DefaultRoutes::new(all_routes().await?);
+    debug!("Default routes:\n{:?}", default_routes);
+    Ok(default_routes)
+}
+
+#[cfg(target_os = "linux")]
+async fn default_route() -> Result<Option<Route>> {
+    let default_route = default_routes().await?.preferred().cloned();
+    match &default_route {
+        Some(route) => debug!("Default route: {}", route.display_named()),
+        None => debug!("No default route"),
+    }
+    Ok(default_route)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn default_route() -> Result<Option<Route>> {
+    let handle = net_route::Handle::new().map_err(Error::RouteError)?;
+    let default_route = handle.default_route().await.map_err(Error::RouteError)?;
+    match default_route {
+        Some(route) => {
+            debug!("Default route:\n{:?}", route);
+            Ok(Some(route.into()))
+        }
+        None => {
+            debug!("No default route");
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) async fn all_routes() -> Result<Vec<Route>> {
+    let handle = net_route::Handle::new().map_err(Error::RouteError)?;
+    let routes = handle.list().await.map_err(Error::RouteError)?;
+    debug!("All routes:\n{:?}", routes);
+    Ok(routes.into_iter().map(|r| r.into()).collect())
+}
+
+/// Get the default route, `None` if there is no default route.
+#[maybe_async::async_impl]
+pub async fn get_default_route() -> Result<Option<Route>> {
+    default_route().await
+}
+
+/// Get the default route, `None` if there is no default route.
+#[maybe_async::sync_impl]
+pub fn get_default_route() -> Result<Option<Route>> {
+    crate::block_on(default_route())
+}
+
+/// Get the default routes of each address family.
+#[maybe_async::async_impl]
+pub async fn get_default_routes() -> Result<DefaultRoutes> {
+    default_routes().await
+}
+
+/// Get the default routes of each address family.
+#[maybe_async::sync_impl]
+pub fn get_default_routes() -> Result<DefaultRoutes> {
+    crate::block_on(default_routes())
+}
+
+/// Get all routes in the routing table.
+#[maybe_async::async_impl]
+pub async fn get_all_routes() -> Result<Vec<Route>> {
+    all_routes().await
+}
+
+/// Get all routes in the routing table.
+#[maybe_async::sync_impl]
+pub fn get_all_routes() -> Result<Vec<Route>> {
+    crate::block_on(all_routes())
 }
 
-#[maybe_async::maybe_async]
-pub async fn get_all_routes() -> Option<Vec<Route>> {
-    if let Ok(handle) = net_route::Handle::new() {
-        let routes = handle.list().await;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_routes() {
+        let route = |destination: &str, prefix, metric| {
+            Route::new(destination.parse().unwrap(), prefix, None, Some(2)).with_metric(metric)
+        };
+        let default_routes = DefaultRoutes::new(vec![
+            route("::", 0, 1024),
+            route("0.0.0.0", 0, 600),
+            route("192.168.1.0", 24, 600),
+            route("0.0.0.0", 0, 100),
+            route("0.0.0.0", 0, 50).with_table(51820),
+            route("::", 0, 10).with_kind(RouteType::Unreachable),
+        ]);
+        assert_eq!(
+            default_routes.ipv4,
+            vec![route("0.0.0.0", 0, 100), route("0.0.0.0", 0, 600)]
+        );
+        assert_eq!(default_routes.ipv6(), Some(&route("::", 0, 1024)));
+        assert_eq!(default_routes.preferred(), Some(&route("0.0.0.0", 0, 100)));
+    }
+
+    #[test]
+    fn lookup() {
+        let route = |destination: &str, prefix, ifindex| {
+            Route::new(destination.parse().unwrap(), prefix, None, Some(ifindex))
+        };
+        assert!(route("10.2.0.0", 16, 2).contains("10.2.3.4".parse().unwrap()));
+        assert!(!route("10.2.0.0", 16, 2).contains("10.3.3.4".parse().unwrap()));
+        assert!(!route("::", 0, 2).contains("10.2.3.4".parse().unwrap()));
+        assert!(route("0.0.0.0", 0, 2).contains("10.2.3.4".parse().unwrap()));
+        // invalid prefixes
+        assert!(!route("10.2.3.4", 33, 2).contains("10.2.3.4".parse().unwrap()));
+        assert_eq!(route("10.2.3.4", 33, 2).mask(), IpAddr::from([255; 4]));
+        assert!(!route("::1", 129, 2).contains("::1".parse().unwrap()));
+
+        let table = RoutingTable::new(vec![
+            route("0.0.0.0", 0, 2).with_metric(100),
+            route("10.0.0.0", 8, 3).with_metric(600),
+            route("10.0.0.0", 8, 4).with_metric(50),
+            route("10.2.3.0", 24, 5).with_table(51820),
+            route("2001:db8::", 32, 6),
+            route("2001:db8:1::", 48, 7).with_kind(RouteType::Blackhole),
+        ]);
+        assert_eq!(
+            table.lookup("10.2.3.4".parse().unwrap()).unwrap().ifindex,
+            Some(4)
+        );
+        assert_eq!(
+            table.lookup("192.0.2.1".parse().unwrap()).unwrap().ifindex,
+            Some(2)
+        );
+        assert_eq!(
+            table
+                .lookup("2001:db8::1".parse().unwrap())
+                .unwrap()
+                .ifindex,
+            Some(6)
+        );
+        assert_eq!(table.lookup("2001:db9::1".parse().unwrap()), None);
+        // only unicast routes forward traffic
+        assert_eq!(
+            table
+                .lookup("2001:db8:1::1".parse().unwrap())
+                .unwrap()
+                .ifindex,
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn display_and_parse() {
+        let route = Route::new(
+            "10.0.0.0".parse().unwrap(),
+            8,
+            Some("192.168.1.1".parse().unwrap()),
+            Some(4_000_000),
+        );
+        assert_eq!(route.to_string(), "10.0.0.0/8 via 192.168.1.1 dev 4000000");
+        assert_eq!(route.to_string().parse::<Route>().unwrap(), route);
+
+        let route = Route::new(Ipv6Addr::UNSPECIFIED.into(), 0, None, None)
+            .with_source("2001:db8::2".parse().unwrap())
+            .with_table(MAIN_TABLE)
+            .with_protocol(RouteProtocol::Ra)
+            .with_scope(RouteScope::Universe)
+            .with_metric(1024);
+        let s = "default table main proto ra scope global src 2001:db8::2 metric 1024";
+        assert_eq!(route.to_string(), s);
+        assert_eq!(s.parse::<Route>().unwrap(), route);
+
+        let unreachable = Route::new(Ipv6Addr::UNSPECIFIED.into(), 0, None, Some(1))
+            .with_kind(RouteType::Unreachable)
+            .with_metric(4278198272);
+        let s = "unreachable ::/0 dev 1 metric 4278198272";
+        assert_eq!(unreachable.to_string(), s);
+        assert_eq!(s.parse::<Route>().unwrap(), unreachable);
 
-        if let Ok(routes) = routes {
-            debug!("All routes:\n{:?}", routes);
-            return Some(routes.into_iter().map(|r| r.into()).collect());
+        let hop = |gateway: &str, ifindex| NextHop {
+            gateway: Some(gateway.parse().unwrap()),
+            ifindex: Some(ifindex),
+            weight: 1,
+        };
+        let multipath = Route::default()
+            .with_metric(100)
+            .with_nexthops(vec![hop("10.0.0.1", 2), hop("10.1.0.1", 3)]);
+        let s = "default metric 100 nexthop via 10.0.0.1 dev 2 weight 1 nexthop via 10.1.0.1 dev 3 weight 1";
+        assert_eq!(multipath.to_string(), s);
+        assert_eq!(s.parse::<Route>().unwrap(), multipath);
+        assert_eq!(
+            multipath.hops(),
+            vec![
+                (Some("10.0.0.1".parse().unwrap()), Some(2)),
+                (Some("10.1.0.1".parse().unwrap()), Some(3))
+            ]
+        );
+
+        let host: Route = "192.0.2.1 table 1000 proto 42".parse().unwrap();
+        assert_eq!(host.prefix, 32);
+        assert_eq!(host.table, Some(1000));
+        assert_eq!(host.protocol, Some(RouteProtocol::Other(42)));
+
+        for invalid in [
+            "",
+            "10.0.0.0/33",
+            "2001:db8::/129",
+            "10.0.0.0/8 via 2001:db8::1",
+            "10.0.0.0/8 via",
+            "10.0.0.0/8 mtu 1500",
+            "10.0.0.0/8 proto nonsense",
+            "default nexthop via 10.0.0.1 weight 0",
+            "default nexthop via 2001:db8::1 nexthop via 10.0.0.1",
+        ] {
+            assert!(
+                matches!(invalid.parse::<Route>(), Err(Error::InvalidRoute(_))),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn cidr() {
+        let route = Route::from_cidr("10.0.0.0/8").unwrap();
+        assert_eq!(route, Route::new([10, 0, 0, 0].into(), 8, None, None));
+        assert_eq!(route.cidr(), "10.0.0.0/8");
+        assert_eq!(
+            Route::from_cidr("2001:db8::1").unwrap().cidr(),
+            "2001:db8::1/128"
+        );
+        assert!(Route::from_cidr("10.0.0.0/33").is_err());
+        assert!(Route::from_cidr("10.0.0.1/8").is_err());
+        assert!(Route::from_cidr("2001:db8::1/64").is_err());
+        assert_eq!(Route::from_cidr("0.0.0.0/0").unwrap().prefix, 0);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn interface_names() {
+        let route = Route::new([127, 0, 0, 0].into(), 8, None, Some(1));
+        assert_eq!(route.to_string(), "127.0.0.0/8 dev 1");
+        assert_eq!(route.display_named().to_string(), "127.0.0.0/8 dev lo");
+        assert!("127.0.0.0/8 dev lo".parse::<Route>().is_err());
+        assert_eq!(Route::parse_named("127.0.0.0/8 dev lo").unwrap(), route);
+    }
+
+    #[test]
+    fn compact_serde() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Watched {
+            #[serde(with = "compact")]
+            route: Route,
         }
-    } else {
-        warn!("Failed to get route handle");
+
+        let watched = Watched {
+            route: "10.0.0.0/8 dev 4000000 metric 100".parse().unwrap(),
+        };
+        let json = serde_json::to_string(&watched).unwrap();
+        assert_eq!(json, r#"{"route":"10.0.0.0/8 dev 4000000 metric 100"}"#);
+        assert_eq!(serde_json::from_str::<Watched>(&json).unwrap(), watched);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn from_message() {
+        use netlink_packet_route::{
+            route::{
+                RouteAddress, RouteAttribute, RouteMessage, RouteNextHop,
+                RouteProtocol as Protocol, RouteType as Kind,
+            },
+            AddressFamily,
+        };
+
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        message.header.table = 254;
+        message.header.protocol = Protocol::Dhcp;
+        message.header.kind = Kind::Unicast;
+        message.attributes = vec![
+            RouteAttribute::Gateway(RouteAddress::Inet(Ipv4Addr::new(192, 168, 1, 1))),
+            RouteAttribute::Oif(2),
+            RouteAttribute::Priority(600),
+            RouteAttribute::PrefSource(RouteAddress::Inet(Ipv4Addr::new(192, 168, 1, 2))),
+            RouteAttribute::Table(1000),
+        ];
+        assert_eq!(
+            route_from_message(message),
+            Some(
+                Route::new(
+                    Ipv4Addr::UNSPECIFIED.into(),
+                    0,
+                    Some(Ipv4Addr::new(192, 168, 1, 1).into()),
+                    Some(2)
+                )
+                .with_metric(600)
+                .with_table(1000)
+                .with_protocol(RouteProtocol::Dhcp)
+                .with_scope(RouteScope::Universe)
+                .with_source(Ipv4Addr::new(192, 168, 1, 2).into())
+                .with_kind(RouteType::Unicast)
+            )
+        );
+
+        // an ECMP default route
+        let hop = |gateway: Ipv4Addr, ifindex, hops| {
+            let mut hop = RouteNextHop::default();
+            hop.interface_index = ifindex;
+            hop.hops = hops;
+            hop.attributes = vec![RouteAttribute::Gateway(RouteAddress::Inet(gateway))];
+            hop
+        };
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        message.header.table = 254;
+        message.header.kind = Kind::Unicast;
+        message.attributes = vec![RouteAttribute::MultiPath(vec![
+            hop(Ipv4Addr::new(10, 0, 0, 1), 2, 0),
+            hop(Ipv4Addr::new(10, 1, 0, 1), 3, 1),
+        ])];
+        let route = route_from_message(message).unwrap();
+        assert_eq!(
+            route.nexthops,
+            vec![
+                NextHop {
+                    gateway: Some(Ipv4Addr::new(10, 0, 0, 1).into()),
+                    ifindex: Some(2),
+                    weight: 1,
+                },
+                NextHop {
+                    gateway: Some(Ipv4Addr::new(10, 1, 0, 1).into()),
+                    ifindex: Some(3),
+                    weight: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            DefaultRoutes::new(vec![route.clone()]).preferred(),
+            Some(&route)
+        );
     }
-    warn!("Failed to get all routes");
-    None
 }
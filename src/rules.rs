@@ -0,0 +1,236 @@
+use crate::error::Result;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+#[cfg(target_os = "linux")]
+use crate::error::Error;
+
+/// What a policy routing rule does with matching traffic, as in `ip rule`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Look up the rule's table.
+    Lookup,
+    /// Jump to the rule with the given priority.
+    Goto(u32),
+    Nop,
+    Blackhole,
+    Unreachable,
+    Prohibit,
+    Other(u8),
+}
+
+/// A policy routing rule.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoutingRule {
+    /// Whether the rule applies to IPv6 instead of IPv4 traffic.
+    pub ipv6: bool,
+    /// Priority, 0 when the kernel omits it.
+    pub priority: u32,
+    /// Source selector, `None` for all sources.
+    pub source: Option<(IpAddr, u8)>,
+    /// Destination selector, `None` for all destinations.
+    pub destination: Option<(IpAddr, u8)>,
+    pub fwmark: Option<u32>,
+    pub fwmask: Option<u32>,
+    pub iif: Option<String>,
+    pub oif: Option<String>,
+    /// The selectors are inverted.
+    pub invert: bool,
+    pub table: Option<u32>,
+    pub action: RuleAction,
+}
+
+/// Policy routing rules of both address families, sorted by family and priority.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutingRules(pub Vec<RoutingRule>);
+
+impl RoutingRules {
+    pub fn new(mut rules: Vec<RoutingRule>) -> Self {
+        rules.sort_by_key(|rule| (rule.ipv6, rule.priority));
+        Self(rules)
+    }
+
+    pub fn diff(&self, other: &Self) -> RoutingRulesDiff {
+        RoutingRulesDiff {
+            added: other
+                .0
+                .iter()
+                .filter(|rule| !self.0.contains(rule))
+                .cloned()
+                .collect(),
+            removed: self
+                .0
+                .iter()
+                .filter(|rule| !other.0.contains(rule))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct RoutingRulesDiff {
+    pub added: Vec<RoutingRule>,
+    pub removed: Vec<RoutingRule>,
+}
+
+#[cfg(target_os = "linux")]
+fn rule_from_message(message: netlink_packet_route::rule::RuleMessage) -> Option<RoutingRule> {
+    use netlink_packet_route::{
+        rule::{RuleAction as Action, RuleAttribute, RuleFlags},
+        AddressFamily,
+    };
+
+    let header = message.header;
+    let ipv6 = match header.family {
+        AddressFamily::Inet => false,
+        AddressFamily::Inet6 => true,
+        _ => return None,
+    };
+    let mut rule = RoutingRule {
+        ipv6,
+        priority: 0,
+        source: None,
+        destination: None,
+        fwmark: None,
+        fwmask: None,
+        iif: None,
+        oif: None,
+        invert: header.flags.contains(RuleFlags::Invert),
+        table: Some(header.table.into()).filter(|table| *table != 0),
+        action: match header.action {
+            Action::ToTable => RuleAction::Lookup,
+            Action::Goto => RuleAction::Goto(0),
+            Action::Nop => RuleAction::Nop,
+            Action::Blackhole => RuleAction::Blackhole,
+            Action::Unreachable => RuleAction::Unreachable,
+            Action::Prohibit => RuleAction::Prohibit,
+            action => RuleAction::Other(action.into()),
+        },
+    };
+    for attribute in message.attributes {
+        match attribute {
+            RuleAttribute::Priority(priority) => rule.priority = priority,
+            RuleAttribute::Source(source) => rule.source = Some((source, header.src_len)),
+            RuleAttribute::Destination(destination) => {
+                rule.destination = Some((destination, header.dst_len))
+            }
+            RuleAttribute::FwMark(fwmark) => rule.fwmark = Some(fwmark),
+            RuleAttribute::FwMask(fwmask) => rule.fwmask = Some(fwmask),
+            RuleAttribute::Iifname(iif) => rule.iif = Some(iif),
+            RuleAttribute::Oifname(oif) => rule.oif = Some(oif),
+            // tables above 255 only fit the attribute
+            RuleAttribute::Table(table) => rule.table = Some(table),
+            RuleAttribute::Goto(target) => rule.action = RuleAction::Goto(target),
+            _ => {}
+        }
+    }
+    Some(rule)
+}
+
+#[cfg(target_os = "linux")]
+async fn routing_rules() -> Result<RoutingRules> {
+    use futures::TryStreamExt;
+
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+    let mut rules = Vec::new();
+    for version in [rtnetlink::IpVersion::V4, rtnetlink::IpVersion::V6] {
+        let mut messages = handle.rule().get(version).execute();
+        while let Some(message) = messages
+            .try_next()
+            .await
+            .map_err(|e| Error::NetlinkError(e.to_string()))?
+        {
+            rules.extend(rule_from_message(message));
+        }
+    }
+    let rules = RoutingRules::new(rules);
+    debug!("Routing rules:\n{:?}", rules);
+    Ok(rules)
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn routing_rules() -> Result<RoutingRules> {
+    debug!("Routing rules are not supported");
+    Err(crate::error::Error::Unsupported(
+        "routing rules".to_string(),
+    ))
+}
+
+/// Get the policy routing rules of both address families.
+#[maybe_async::async_impl]
+pub async fn get_routing_rules() -> Result<RoutingRules> {
+    routing_rules().await
+}
+
+/// Get the policy routing rules of both address families.
+#[maybe_async::sync_impl]
+pub fn get_routing_rules() -> Result<RoutingRules> {
+    crate::block_on(routing_rules())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(priority: u32, table: u32, fwmark: Option<u32>) -> RoutingRule {
+        RoutingRule {
+            ipv6: false,
+            priority,
+            source: None,
+            destination: None,
+            fwmark,
+            fwmask: None,
+            iif: None,
+            oif: None,
+            invert: false,
+            table: Some(table),
+            action: RuleAction::Lookup,
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let main = rule(32766, 254, None);
+        let old = RoutingRules::new(vec![main.clone(), rule(0, 255, None)]);
+        let new = RoutingRules::new(vec![
+            rule(0, 255, None),
+            rule(32765, 51820, Some(0xca6c)),
+            main,
+        ]);
+        assert_eq!(new.0[1], rule(32765, 51820, Some(0xca6c)));
+        assert_eq!(
+            old.diff(&new),
+            RoutingRulesDiff {
+                added: vec![rule(32765, 51820, Some(0xca6c))],
+                removed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn from_message() {
+        use netlink_packet_route::{
+            rule::{RuleAction as Action, RuleAttribute, RuleFlags, RuleMessage},
+            AddressFamily,
+        };
+
+        let mut message = RuleMessage::default();
+        message.header.family = AddressFamily::Inet;
+        message.header.table = 252;
+        message.header.action = Action::ToTable;
+        message.header.flags = RuleFlags::Invert;
+        message.attributes = vec![
+            RuleAttribute::Priority(32765),
+            RuleAttribute::FwMark(0xca6c),
+            RuleAttribute::Table(51820),
+        ];
+        let mut expected = rule(32765, 51820, Some(0xca6c));
+        expected.invert = true;
+        assert_eq!(rule_from_message(message), Some(expected));
+    }
+}
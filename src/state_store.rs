@@ -0,0 +1,257 @@
+//! # 💾 Persistence backends for the observed network state
+
+use crate::error::{Error, Result};
+use log::trace;
+use std::{
+    fmt::Debug,
+    fs::{self, File},
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Storage backend for the persisted [`NetworkState`](crate::NetworkState).
+///
+/// Stores deal in opaque bytes, encoding and encryption are handled by the caller.
+pub trait StateStore: Debug + Send + Sync {
+    /// Persist the encoded state, replacing any previously stored state.
+    fn save(&self, data: &[u8]) -> Result<()>;
+
+    /// Load the previously persisted state.
+    fn load(&self) -> Result<Vec<u8>>;
+
+    /// A store for other data named `name` kept alongside this one, such as the known networks.
+    ///
+    /// `None` if the backend has no such place, the default.
+    fn sibling(&self, _name: &str) -> Option<Arc<dyn StateStore>> {
+        None
+    }
+}
+
+/// Stores the state in a file, by default in the user's data directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl Default for FileStore {
+    fn default() -> Self {
+        Self::new(get_data_path())
+    }
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl StateStore for FileStore {
+    fn save(&self, data: &[u8]) -> Result<()> {
+        trace!("Saving state to {}", self.path.display());
+        let mut file = File::create(&self.path)?;
+        file.write_all(data)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<u8>> {
+        trace!("Loading state from {}", self.path.display());
+        let mut file = File::open(&self.path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn sibling(&self, name: &str) -> Option<Arc<dyn StateStore>> {
+        Some(Arc::new(Self::new(self.path.with_file_name(name))))
+    }
+}
+
+/// Keeps the state in memory.
+///
+/// Clones share the same storage, so a clone can be handed to the observer
+/// while the original is kept to inspect or seed the stored state.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    data: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn save(&self, data: &[u8]) -> Result<()> {
+        *self.data.lock().unwrap_or_else(|e| e.into_inner()) = Some(data.to_vec());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<u8>> {
+        self.data
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or(Error::StateNotFound)
+    }
+}
+
+/// Stores the state as a row in a SQLite database.
+///
+/// Several observers can share one database by using different keys.
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+    key: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open or create the database at `path`.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::from_connection(rusqlite::Connection::open(path)?)
+    }
+
+    /// Open a private in-memory database.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(rusqlite::Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the state table if needed.
+    pub fn from_connection(connection: rusqlite::Connection) -> Result<Self> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS network_state (
+                key TEXT PRIMARY KEY NOT NULL,
+                data BLOB NOT NULL
+            )",
+            (),
+        )?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            key: env!("CARGO_PKG_NAME").to_string(),
+        })
+    }
+
+    /// Set the key the state is stored under.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl StateStore for SqliteStore {
+    fn save(&self, data: &[u8]) -> Result<()> {
+        trace!("Saving state to database with key {}", self.key);
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        connection.execute(
+            "INSERT INTO network_state (key, data) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET data = excluded.data",
+            (&self.key, data),
+        )?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<u8>> {
+        use rusqlite::OptionalExtension;
+
+        trace!("Loading state from database with key {}", self.key);
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        connection
+            .query_row(
+                "SELECT data FROM network_state WHERE key = ?1",
+                [&self.key],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(Error::StateNotFound)
+    }
+
+    /// A store on the same connection, keyed `<key>/<name>`.
+    fn sibling(&self, name: &str) -> Option<Arc<dyn StateStore>> {
+        Some(Arc::new(Self {
+            connection: self.connection.clone(),
+            key: format!("{}/{}", self.key, name),
+        }))
+    }
+}
+
+pub fn get_data_path() -> String {
+    get_data_file_path("state.cache")
+}
+
+/// Path of `file_name` in the crate's data directory.
+pub fn get_data_file_path(file_name: &str) -> String {
+    if let Some(base_dirs) = directories::ProjectDirs::from("", "", env!("CARGO_PKG_NAME")) {
+        let mut dir = base_dirs.data_dir();
+        // Create directory if it doesn't exist
+        if !dir.exists() && fs::create_dir_all(dir).is_err() {
+            // If we can't create the data directory, fallback to cache directory
+            dir = base_dirs.cache_dir();
+            if !dir.exists() && fs::create_dir_all(dir).is_err() {
+                dir = base_dirs.config_dir();
+            }
+        }
+        if let Some(path) = dir.join(file_name).to_str() {
+            return path.to_string();
+        }
+    };
+    // If we can't get any directory, fallback to current directory
+    file_name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-file-store.cache",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let store = FileStore::new(&path);
+        store.save(b"hello world").unwrap();
+        assert_eq!(store.load().unwrap(), b"hello world");
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(store.load(), Err(Error::FileError(_))));
+
+        let sibling = store.sibling("other.json").unwrap();
+        sibling.save(b"other").unwrap();
+        let sibling_path = path.with_file_name("other.json");
+        assert_eq!(fs::read(&sibling_path).unwrap(), b"other");
+        fs::remove_file(sibling_path).unwrap();
+    }
+
+    #[test]
+    fn memory_store() {
+        let store = MemoryStore::new();
+        assert!(matches!(store.load(), Err(Error::StateNotFound)));
+        store.clone().save(b"hello world").unwrap();
+        assert_eq!(store.load().unwrap(), b"hello world");
+        assert!(store.sibling("other").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn sqlite_store() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        assert!(matches!(store.load(), Err(Error::StateNotFound)));
+        store.save(b"hello").unwrap();
+        store.save(b"hello world").unwrap();
+        assert_eq!(store.load().unwrap(), b"hello world");
+
+        let sibling = store.sibling("other").unwrap();
+        assert!(matches!(sibling.load(), Err(Error::StateNotFound)));
+        sibling.save(b"other").unwrap();
+        assert_eq!(store.load().unwrap(), b"hello world");
+        assert_eq!(sibling.load().unwrap(), b"other");
+    }
+}
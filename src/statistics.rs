@@ -0,0 +1,217 @@
+use crate::NetworkChange;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::SystemTime};
+
+/// Traffic counters of an interface.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+/// Per second rates between two readings of the counters.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterfaceRates {
+    pub rx_bytes: f64,
+    pub tx_bytes: f64,
+    /// Receive and transmit errors.
+    pub errors: f64,
+    /// Received and transmitted packets that were dropped.
+    pub dropped: f64,
+}
+
+/// Thresholds on the interface rates, crossing one is reported as a change.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StatisticsThresholds {
+    /// Errors per second.
+    pub error_rate: Option<f64>,
+    /// Dropped packets per second.
+    pub drop_rate: Option<f64>,
+    /// Bytes per second, received and transmitted, below which an interface is idle.
+    pub idle_rate: Option<f64>,
+}
+
+impl StatisticsThresholds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = Some(error_rate);
+        self
+    }
+
+    pub fn with_drop_rate(mut self, drop_rate: f64) -> Self {
+        self.drop_rate = Some(drop_rate);
+        self
+    }
+
+    pub fn with_idle_rate(mut self, idle_rate: f64) -> Self {
+        self.idle_rate = Some(idle_rate);
+        self
+    }
+}
+
+/// Counters of all interfaces read at the same time, with the rates since the previous reading.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub time: SystemTime,
+    pub counters: HashMap<String, InterfaceCounters>,
+    pub rates: HashMap<String, InterfaceRates>,
+}
+
+impl Statistics {
+    pub fn new(counters: HashMap<String, InterfaceCounters>) -> Self {
+        Self {
+            time: SystemTime::now(),
+            counters,
+            rates: HashMap::new(),
+        }
+    }
+
+    /// Compute the rates since `previous`, skipping interfaces whose counters were reset.
+    ///
+    /// No rates are computed when the clock did not move forward since `previous`, such as
+    /// after the wall clock was set back.
+    pub fn with_rates_since(mut self, previous: &Self) -> Self {
+        let elapsed = match self.time.duration_since(previous.time) {
+            Ok(elapsed) if !elapsed.is_zero() => elapsed.as_secs_f64(),
+            _ => return self,
+        };
+        self.rates = self
+            .counters
+            .iter()
+            .filter_map(|(name, new)| {
+                let old = previous.counters.get(name)?;
+                let rate = |new: u64, old: u64| Some(new.checked_sub(old)? as f64 / elapsed);
+                let rates = InterfaceRates {
+                    rx_bytes: rate(new.rx_bytes, old.rx_bytes)?,
+                    tx_bytes: rate(new.tx_bytes, old.tx_bytes)?,
+                    errors: rate(
+                        new.rx_errors.saturating_add(new.tx_errors),
+                        old.rx_errors.saturating_add(old.tx_errors),
+                    )?,
+                    dropped: rate(
+                        new.rx_dropped.saturating_add(new.tx_dropped),
+                        old.rx_dropped.saturating_add(old.tx_dropped),
+                    )?,
+                };
+                Some((name.clone(), rates))
+            })
+            .collect();
+        self
+    }
+
+    /// Report the first threshold crossed since `self`.
+    pub fn compare(&self, other: &Self, thresholds: &StatisticsThresholds) -> NetworkChange {
+        let above = |rate: f64, threshold: Option<f64>| threshold.is_some_and(|t| rate > t);
+        let below = |rate: f64, threshold: Option<f64>| threshold.is_some_and(|t| rate < t);
+        let mut names: Vec<&String> = other.rates.keys().collect();
+        names.sort();
+        for name in names {
+            let new = &other.rates[name];
+            let old = self.rates.get(name);
+            let errors = |r: &InterfaceRates| above(r.errors, thresholds.error_rate);
+            let dropped = |r: &InterfaceRates| above(r.dropped, thresholds.drop_rate);
+            let idle = |r: &InterfaceRates| below(r.rx_bytes + r.tx_bytes, thresholds.idle_rate);
+            if errors(new) && !old.is_some_and(errors) {
+                return NetworkChange::ErrorRate(name.clone());
+            }
+            if dropped(new) && !old.is_some_and(dropped) {
+                return NetworkChange::DropRate(name.clone());
+            }
+            // an interface is only reported going idle after being seen active
+            if idle(new) && old.is_some_and(|r| !idle(r)) {
+                return NetworkChange::Idle(name.clone());
+            }
+        }
+        NetworkChange::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn reading(time: SystemTime, bytes: u64, errors: u64) -> Statistics {
+        let counters = InterfaceCounters {
+            rx_bytes: bytes,
+            tx_bytes: bytes,
+            rx_errors: errors,
+            ..Default::default()
+        };
+        Statistics {
+            time,
+            counters: vec![("eth0".to_string(), counters)].into_iter().collect(),
+            rates: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn rates() {
+        let start = SystemTime::now();
+        let first = reading(start, 1000, 0);
+        let second = reading(start + Duration::from_secs(10), 2000, 5).with_rates_since(&first);
+        assert_eq!(
+            second.rates["eth0"],
+            InterfaceRates {
+                rx_bytes: 100.0,
+                tx_bytes: 100.0,
+                errors: 0.5,
+                dropped: 0.0,
+            }
+        );
+
+        // counters reset
+        let third = reading(start + Duration::from_secs(20), 0, 0).with_rates_since(&second);
+        assert!(third.rates.is_empty());
+
+        // clock set back
+        let earlier = reading(start, 3000, 5).with_rates_since(&second);
+        assert!(earlier.rates.is_empty());
+
+        // counters near the limit
+        let mut full = reading(start, u64::MAX, u64::MAX);
+        full.counters.get_mut("eth0").unwrap().tx_errors = u64::MAX;
+        let mut later = reading(start + Duration::from_secs(10), u64::MAX, u64::MAX);
+        later.counters.get_mut("eth0").unwrap().tx_errors = u64::MAX;
+        assert_eq!(later.with_rates_since(&full).rates["eth0"].errors, 0.0);
+    }
+
+    #[test]
+    fn thresholds() {
+        let thresholds = StatisticsThresholds::new()
+            .with_error_rate(0.1)
+            .with_idle_rate(10.0);
+        let start = SystemTime::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let first = reading(at(0), 0, 0);
+        let busy = reading(at(10), 10000, 0).with_rates_since(&first);
+        let errors = reading(at(20), 20000, 10).with_rates_since(&busy);
+        let more_errors = reading(at(30), 30000, 20).with_rates_since(&errors);
+        let idle = reading(at(40), 30000, 20).with_rates_since(&more_errors);
+
+        assert_eq!(first.compare(&busy, &thresholds), NetworkChange::None);
+        assert_eq!(
+            busy.compare(&errors, &thresholds),
+            NetworkChange::ErrorRate("eth0".to_string())
+        );
+        // still above the threshold
+        assert_eq!(
+            errors.compare(&more_errors, &thresholds),
+            NetworkChange::None
+        );
+        assert_eq!(
+            more_errors.compare(&idle, &thresholds),
+            NetworkChange::Idle("eth0".to_string())
+        );
+        assert_eq!(idle.compare(&idle, &thresholds), NetworkChange::None);
+    }
+}
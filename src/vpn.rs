@@ -0,0 +1,329 @@
+use crate::{
+    error::Result,
+    routes::{self, Route, RouteProtocol, RouteScope, LOCAL_TABLE},
+    NetworkChange,
+};
+use log::debug;
+#[cfg(not(target_os = "linux"))]
+use netdev::Interface;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Kind of a tunnel interface.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelKind {
+    Tun,
+    Tap,
+    WireGuard,
+    Ipsec,
+    /// A tunnel of another kind, such as OpenVPN's data channel offload.
+    Other,
+}
+
+impl TunnelKind {
+    /// Classify a link by its kind, as in `ip -d link`, and whether it has a link-layer address,
+    /// which tap devices of kind `tun` do. GRE, SIT and other plain tunnels are not VPNs.
+    pub fn classify(kind: Option<&str>, has_mac: bool) -> Option<Self> {
+        match kind? {
+            "tun" if has_mac => Some(Self::Tap),
+            "tun" => Some(Self::Tun),
+            "wireguard" => Some(Self::WireGuard),
+            "xfrm" | "vti" | "vti6" => Some(Self::Ipsec),
+            // OpenVPN data channel offload
+            "ovpn" | "ovpn-dco" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    /// Classify a local interface by name, `None` if it is not a tunnel.
+    #[cfg(not(target_os = "linux"))]
+    pub fn of(interface: &Interface) -> Option<Self> {
+        if interface.name.starts_with("utun") {
+            return Some(Self::Tun);
+        }
+        if interface.name.starts_with("ipsec") {
+            return Some(Self::Ipsec);
+        }
+        None
+    }
+}
+
+/// A tunnel interface that traffic is routed through.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Vpn {
+    pub interface: String,
+    pub ifindex: u32,
+    pub kind: TunnelKind,
+    /// The default route, or the two halves of the address space that OpenVPN's `def1`
+    /// uses to override it, point at the tunnel. Otherwise only the routes are tunnelled.
+    pub default_route: bool,
+    /// Routes pointing at the tunnel.
+    pub routes: Vec<Route>,
+}
+
+/// Connected VPNs sorted by interface name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Vpns(pub Vec<Vpn>);
+
+impl Vpns {
+    /// Find the tunnels among `tunnels` that routes point at.
+    ///
+    /// Local routes and the routes the kernel adds for the tunnel's own addresses are ignored.
+    pub fn new(tunnels: Vec<(String, u32, TunnelKind)>, routes: &[Route]) -> Self {
+        let mut vpns: Vec<Vpn> = tunnels
+            .into_iter()
+            .filter_map(|(interface, ifindex, kind)| {
+                let routes: Vec<Route> = routes
+                    .iter()
+                    .filter(|route| {
+                        route.hops().iter().any(|(_, hop)| *hop == Some(ifindex))
+                            && is_tunnelled(route)
+                    })
+                    .cloned()
+                    .collect();
+                if routes.is_empty() {
+                    return None;
+                }
+                Some(Vpn {
+                    interface,
+                    ifindex,
+                    kind,
+                    default_route: covers_default(&routes),
+                    routes,
+                })
+            })
+            .collect();
+        vpns.sort_by(|a, b| a.interface.cmp(&b.interface));
+        Self(vpns)
+    }
+
+    pub fn get(&self, interface: &str) -> Option<&Vpn> {
+        self.0.iter().find(|vpn| vpn.interface == interface)
+    }
+
+    /// Whether the default route points at a tunnel.
+    pub fn default_route(&self) -> bool {
+        self.0.iter().any(|vpn| vpn.default_route)
+    }
+
+    /// Report the first tunnel that was connected, disconnected or whose routes changed.
+    pub fn compare(&self, other: &Self) -> NetworkChange {
+        if let Some(vpn) = other
+            .0
+            .iter()
+            .find(|vpn| self.get(&vpn.interface).is_none())
+        {
+            return NetworkChange::VpnConnected(vpn.clone());
+        }
+        if let Some(vpn) = self
+            .0
+            .iter()
+            .find(|vpn| other.get(&vpn.interface).is_none())
+        {
+            return NetworkChange::VpnDisconnected(vpn.clone());
+        }
+        // such as a switch between split and full tunnel
+        if let Some(vpn) = other
+            .0
+            .iter()
+            .find(|vpn| self.get(&vpn.interface).is_some_and(|old| old != *vpn))
+        {
+            return NetworkChange::VpnChanged(vpn.clone());
+        }
+        NetworkChange::None
+    }
+}
+
+/// Whether `route` carries traffic into the tunnel, rather than reaching its own addresses.
+fn is_tunnelled(route: &Route) -> bool {
+    let link_local = match route.destination {
+        IpAddr::V6(destination) => destination.is_unicast_link_local() && route.prefix >= 10,
+        IpAddr::V4(_) => false,
+    };
+    let kernel = route.protocol == Some(RouteProtocol::Kernel)
+        && (matches!(route.scope, Some(RouteScope::Host | RouteScope::Link)) || link_local);
+    route.is_unicast() && route.table != Some(LOCAL_TABLE) && !kernel
+}
+
+/// Whether `routes` include a default route or both halves of an address family.
+fn covers_default(routes: &[Route]) -> bool {
+    let half = |destination: IpAddr| {
+        routes
+            .iter()
+            .any(|route| route.prefix == 1 && route.destination == destination)
+    };
+    routes.iter().any(|route| route.prefix == 0)
+        || (half(Ipv4Addr::UNSPECIFIED.into()) && half(Ipv4Addr::new(128, 0, 0, 0).into()))
+        || (half(Ipv6Addr::UNSPECIFIED.into())
+            && half(Ipv6Addr::new(0x8000, 0, 0, 0, 0, 0, 0, 0).into()))
+}
+
+/// Tunnel interfaces by name, index and kind, in the namespace of the calling thread.
+#[cfg(target_os = "linux")]
+async fn tunnels() -> Result<Vec<(String, u32, TunnelKind)>> {
+    let links = crate::link::links().await?;
+    Ok(netdev::get_interfaces()
+        .into_iter()
+        .filter_map(|interface| {
+            let kind = links.get(&interface.name)?.properties.kind.as_deref();
+            let has_mac = interface
+                .mac_addr
+                .is_some_and(|mac| mac != netdev::mac::MacAddr::zero());
+            TunnelKind::classify(kind, has_mac).map(|kind| (interface.name, interface.index, kind))
+        })
+        .collect())
+}
+
+/// Tunnel interfaces by name, index and kind.
+#[cfg(not(target_os = "linux"))]
+async fn tunnels() -> Result<Vec<(String, u32, TunnelKind)>> {
+    Ok(netdev::get_interfaces()
+        .into_iter()
+        .filter_map(|interface| {
+            TunnelKind::of(&interface).map(|kind| (interface.name, interface.index, kind))
+        })
+        .collect())
+}
+
+async fn vpns() -> Result<Vpns> {
+    let tunnels = tunnels().await?;
+    if tunnels.is_empty() {
+        debug!("No tunnel interfaces");
+        return Ok(Vpns::default());
+    }
+    let vpns = Vpns::new(tunnels, &routes::all_routes().await?);
+    debug!("VPNs:\n{:?}", vpns);
+    Ok(vpns)
+}
+
+/// Get the tunnel interfaces that routes point at.
+#[maybe_async::async_impl]
+pub async fn get_vpns() -> Result<Vpns> {
+    vpns().await
+}
+
+/// Get the tunnel interfaces that routes point at.
+#[maybe_async::sync_impl]
+pub fn get_vpns() -> Result<Vpns> {
+    crate::block_on(vpns())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        assert_eq!(
+            TunnelKind::classify(Some("tun"), false),
+            Some(TunnelKind::Tun)
+        );
+        assert_eq!(
+            TunnelKind::classify(Some("tun"), true),
+            Some(TunnelKind::Tap)
+        );
+        assert_eq!(
+            TunnelKind::classify(Some("wireguard"), false),
+            Some(TunnelKind::WireGuard)
+        );
+        assert_eq!(
+            TunnelKind::classify(Some("xfrm"), false),
+            Some(TunnelKind::Ipsec)
+        );
+        // uplinks and plain tunnels
+        for kind in [
+            None,
+            Some("bridge"),
+            Some("gre"),
+            Some("sit"),
+            Some("ip6tnl"),
+        ] {
+            assert_eq!(TunnelKind::classify(kind, false), None);
+        }
+    }
+
+    #[test]
+    fn detect_and_compare() {
+        let route = |destination: &str, prefix, ifindex| {
+            Route::new(destination.parse().unwrap(), prefix, None, Some(ifindex))
+        };
+        let lan = route("192.168.1.0", 24, 2);
+        let split = route("10.0.0.0", 8, 5);
+        let full = route("0.0.0.0", 0, 6);
+        let tunnels = vec![
+            ("tun0".to_string(), 5, TunnelKind::Tun),
+            ("wg0".to_string(), 6, TunnelKind::WireGuard),
+        ];
+
+        let none = Vpns::new(tunnels.clone(), std::slice::from_ref(&lan));
+        assert_eq!(none, Vpns::default());
+
+        let split_tunnel = Vpns::new(tunnels.clone(), &[lan.clone(), split.clone()]);
+        assert!(!split_tunnel.default_route());
+        assert_eq!(split_tunnel.get("tun0").unwrap().routes, vec![split]);
+
+        let full_tunnel = Vpns::new(tunnels, &[lan, full]);
+        assert!(full_tunnel.default_route());
+
+        let wg0 = full_tunnel.get("wg0").unwrap().clone();
+        let tun0 = split_tunnel.get("tun0").unwrap().clone();
+        assert_eq!(
+            split_tunnel.compare(&full_tunnel),
+            NetworkChange::VpnConnected(wg0.clone())
+        );
+        assert_eq!(
+            split_tunnel.compare(&none),
+            NetworkChange::VpnDisconnected(tun0)
+        );
+        assert_eq!(full_tunnel.compare(&full_tunnel), NetworkChange::None);
+
+        // wg0 goes from carrying some routes to the default route
+        let wg0_split = Vpns::new(
+            vec![("wg0".to_string(), 6, TunnelKind::WireGuard)],
+            &[route("10.0.0.0", 8, 6)],
+        );
+        assert_eq!(
+            wg0_split.compare(&full_tunnel),
+            NetworkChange::VpnChanged(wg0)
+        );
+    }
+
+    #[test]
+    fn ignore_local_and_kernel_routes() {
+        let tunnels = vec![("tun0".to_string(), 5, TunnelKind::Tun)];
+        let route = |destination: &str, prefix| {
+            Route::new(destination.parse().unwrap(), prefix, None, Some(5))
+        };
+        let local = route("10.8.0.2", 32)
+            .with_table(LOCAL_TABLE)
+            .with_protocol(RouteProtocol::Kernel)
+            .with_scope(RouteScope::Host);
+        let subnet = route("10.8.0.0", 24)
+            .with_protocol(RouteProtocol::Kernel)
+            .with_scope(RouteScope::Link);
+        let link_local = route("fe80::", 64)
+            .with_protocol(RouteProtocol::Kernel)
+            .with_scope(RouteScope::Universe);
+        assert_eq!(
+            Vpns::new(tunnels.clone(), &[local, subnet, link_local]),
+            Vpns::default()
+        );
+
+        let pushed = route("10.9.0.0", 16).with_protocol(RouteProtocol::Boot);
+        let vpns = Vpns::new(tunnels, std::slice::from_ref(&pushed));
+        assert_eq!(vpns.get("tun0").unwrap().routes, vec![pushed]);
+    }
+
+    #[test]
+    fn def1_is_a_default_route() {
+        let tunnels = vec![("tun0".to_string(), 5, TunnelKind::Tun)];
+        let route = |destination: &str| Route::new(destination.parse().unwrap(), 1, None, Some(5));
+
+        let v4 = Vpns::new(tunnels.clone(), &[route("0.0.0.0"), route("128.0.0.0")]);
+        assert!(v4.default_route());
+        let v6 = Vpns::new(tunnels.clone(), &[route("::"), route("8000::")]);
+        assert!(v6.default_route());
+        let half = Vpns::new(tunnels, &[route("0.0.0.0")]);
+        assert!(!half.default_route());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{error::Result, neighbours::MacAddr, NetworkChange};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "linux")]
+use crate::error::Error;
+
+/// Association of a wireless interface.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WirelessInterface {
+    pub name: String,
+    pub ifindex: u32,
+    /// Network name, `None` when not associated.
+    pub ssid: Option<String>,
+    /// Address of the access point.
+    pub bssid: Option<MacAddr>,
+    /// Frequency in MHz.
+    pub frequency: Option<u32>,
+    /// Signal level in dBm.
+    pub signal: Option<i8>,
+}
+
+/// Wireless interfaces sorted by name.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WirelessInterfaces(pub Vec<WirelessInterface>);
+
+impl WirelessInterfaces {
+    pub fn new(mut interfaces: Vec<WirelessInterface>) -> Self {
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        Self(interfaces)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WirelessInterface> {
+        self.0.iter().find(|i| i.name == name)
+    }
+
+    /// Compare associations, ignoring frequency and signal level.
+    pub fn compare(&self, other: &Self) -> NetworkChange {
+        let names = self.0.iter().chain(other.0.iter()).map(|i| &i.name);
+        for name in names {
+            let old = self.get(name);
+            let new = other.get(name);
+            let ssid = |i: Option<&WirelessInterface>| i.and_then(|i| i.ssid.clone());
+            let bssid = |i: Option<&WirelessInterface>| i.and_then(|i| i.bssid);
+            if ssid(old) != ssid(new) {
+                return NetworkChange::WifiSsid(name.clone());
+            }
+            if bssid(old) != bssid(new) {
+                return NetworkChange::WifiRoamed(name.clone());
+            }
+        }
+        NetworkChange::None
+    }
+}
+
+/// Names of the interfaces with a `/sys/class/net/<if>/wireless` directory.
+#[cfg(target_os = "linux")]
+fn wireless_interface_names() -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir("/sys/class/net")? {
+        let entry = entry?;
+        if entry.path().join("wireless").exists() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(target_os = "linux")]
+async fn wireless_interfaces() -> Result<WirelessInterfaces> {
+    use futures::TryStreamExt;
+    use wl_nl80211::{Nl80211Attr, Nl80211StationInfo};
+
+    let names = wireless_interface_names()?;
+    if names.is_empty() {
+        return Ok(WirelessInterfaces::default());
+    }
+    let netlink_error = |e: wl_nl80211::Nl80211Error| Error::NetlinkError(e.to_string());
+
+    let (connection, handle, _) =
+        wl_nl80211::new_connection().map_err(|e| Error::NetlinkError(e.to_string()))?;
+    tokio::spawn(connection);
+
+    let mut interfaces = Vec::new();
+    let mut messages = handle.interface().get(Vec::new()).execute().await;
+    while let Some(message) = messages.try_next().await.map_err(netlink_error)? {
+        let mut interface = WirelessInterface {
+            name: String::new(),
+            ifindex: 0,
+            ssid: None,
+            bssid: None,
+            frequency: None,
+            signal: None,
+        };
+        for attribute in message.payload.attributes {
+            match attribute {
+                Nl80211Attr::IfName(name) => interface.name = name,
+                Nl80211Attr::IfIndex(ifindex) => interface.ifindex = ifindex,
+                Nl80211Attr::Ssid(ssid) => interface.ssid = Some(ssid),
+                Nl80211Attr::WiphyFreq(frequency) => interface.frequency = Some(frequency),
+                _ => {}
+            }
+        }
+        if names.contains(&interface.name) {
+            interfaces.push(interface);
+        }
+    }
+
+    // a client interface lists its access point as the station
+    for interface in interfaces.iter_mut().filter(|i| i.ssid.is_some()) {
+        let mut messages = handle.station().dump(interface.ifindex).execute().await;
+        while let Some(message) = messages.try_next().await.map_err(netlink_error)? {
+            for attribute in message.payload.attributes {
+                match attribute {
+                    Nl80211Attr::Mac(mac) => interface.bssid = Some(MacAddr::from_octets(mac)),
+                    Nl80211Attr::StationInfo(info) => {
+                        interface.signal = info.into_iter().find_map(|i| match i {
+                            Nl80211StationInfo::Signal(signal) => Some(signal),
+                            _ => None,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    debug!("Wireless interfaces:\n{:?}", interfaces);
+    Ok(WirelessInterfaces::new(interfaces))
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn wireless_interfaces() -> Result<WirelessInterfaces> {
+    debug!("Wireless observation is not supported");
+    Err(crate::error::Error::Unsupported(
+        "wireless observation".to_string(),
+    ))
+}
+
+/// Get the association of all wireless interfaces.
+#[maybe_async::async_impl]
+pub async fn get_wireless_interfaces() -> Result<WirelessInterfaces> {
+    wireless_interfaces().await
+}
+
+/// Get the association of all wireless interfaces.
+#[maybe_async::sync_impl]
+pub fn get_wireless_interfaces() -> Result<WirelessInterfaces> {
+    crate::block_on(wireless_interfaces())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wlan(ssid: Option<&str>, bssid: u8, signal: i8) -> WirelessInterface {
+        WirelessInterface {
+            name: "wlan0".to_string(),
+            ifindex: 3,
+            ssid: ssid.map(str::to_string),
+            bssid: ssid.map(|_| MacAddr::new(0, 0, 0, 0, 0, bssid)),
+            frequency: Some(2412),
+            signal: Some(signal),
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let old = WirelessInterfaces::new(vec![wlan(Some("office"), 1, -50)]);
+        let weaker = WirelessInterfaces::new(vec![wlan(Some("office"), 1, -70)]);
+        let roamed = WirelessInterfaces::new(vec![wlan(Some("office"), 2, -50)]);
+        let switched = WirelessInterfaces::new(vec![wlan(Some("guest"), 3, -50)]);
+        let disconnected = WirelessInterfaces::new(vec![wlan(None, 0, -50)]);
+
+        assert_eq!(old.compare(&weaker), NetworkChange::None);
+        assert_eq!(
+            old.compare(&roamed),
+            NetworkChange::WifiRoamed("wlan0".to_string())
+        );
+        assert_eq!(
+            old.compare(&switched),
+            NetworkChange::WifiSsid("wlan0".to_string())
+        );
+        assert_eq!(
+            old.compare(&disconnected),
+            NetworkChange::WifiSsid("wlan0".to_string())
+        );
+        assert_eq!(
+            WirelessInterfaces::default().compare(&old),
+            NetworkChange::WifiSsid("wlan0".to_string())
+        );
+    }
+}